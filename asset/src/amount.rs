@@ -0,0 +1,169 @@
+use std::fmt::Display;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+/// Largest number of decimals an amount can be displayed with, 10^19 being
+/// the largest power of ten fitting in a u64.
+pub const MAX_DECIMALS: u8 = 19;
+
+/// An amount of asset units.
+/// All arithmetic is checked so an overflow can't silently wrap around.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const MAX: Amount = Amount(u64::MAX);
+
+    pub const fn from_units(units: u64) -> Self {
+        Amount(units)
+    }
+
+    pub const fn to_units(self) -> u64 {
+        self.0
+    }
+
+    pub fn checked_add(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_add(rhs.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_sub(rhs.0).map(Amount)
+    }
+
+    pub fn checked_mul(self, rhs: u64) -> Option<Amount> {
+        self.0.checked_mul(rhs).map(Amount)
+    }
+
+    /// Sums all the amounts, returns `None` on overflow.
+    pub fn checked_sum<I: IntoIterator<Item = Amount>>(amounts: I) -> Option<Amount> {
+        amounts.into_iter().try_fold(Amount::ZERO, Amount::checked_add)
+    }
+
+    /// Displays the amount as a decimal number where the last `decimals`
+    /// digits are the fractional part, e.g. 150 units with 2 decimals is
+    /// shown as `1.50`.
+    pub fn display_decimal(self, decimals: u8) -> DecimalAmount {
+        DecimalAmount { units: self.0, decimals }
+    }
+
+    /// Parses a decimal number such as `1.5` into units, given the number of
+    /// decimals of the asset. More fractional digits than `decimals` is an
+    /// error rather than a silent truncation.
+    pub fn from_decimal(s: &str, decimals: u8) -> anyhow::Result<Amount> {
+        if decimals > MAX_DECIMALS {
+            bail!("Too many decimals {}, max is {}", decimals, MAX_DECIMALS);
+        }
+
+        let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+
+        if int.is_empty() || !int.bytes().all(|b| b.is_ascii_digit()) || !frac.bytes().all(|b| b.is_ascii_digit()) {
+            bail!("Invalid decimal amount {:?}", s);
+        }
+        if frac.len() > decimals as usize {
+            bail!("Amount {:?} has more than {} decimals", s, decimals);
+        }
+
+        // Right pad the fractional part so it is expressed in units.
+        let frac_units = 10u64.pow((decimals as usize - frac.len()) as u32);
+        let frac: u64 = if frac.is_empty() { 0 } else { frac.parse::<u64>().context("Fractional part out of range")? * frac_units };
+        let int: u64 = int.parse().context("Integer part out of range")?;
+
+        int.checked_mul(10u64.pow(decimals as u32))
+            .and_then(|units| units.checked_add(frac))
+            .map(Amount)
+            .with_context(|| format!("Amount {:?} overflows", s))
+    }
+}
+
+impl From<u64> for Amount {
+    fn from(units: u64) -> Self {
+        Amount(units)
+    }
+}
+
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> Self {
+        amount.0
+    }
+}
+
+impl Display for Amount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// See [`Amount::display_decimal`].
+pub struct DecimalAmount {
+    units: u64,
+    decimals: u8,
+}
+
+impl Display for DecimalAmount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.decimals == 0 {
+            return self.units.fmt(f);
+        }
+
+        let decimals = self.decimals as usize;
+        let digits = format!("{:0>width$}", self.units, width = decimals + 1);
+        let (int, frac) = digits.split_at(digits.len() - decimals);
+
+        write!(f, "{}.{}", int, frac)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Amount;
+
+    #[test]
+    fn checked_arithmetic() {
+        let a = Amount::from_units(10);
+
+        assert_eq!(a.checked_add(Amount::from_units(5)), Some(Amount::from_units(15)));
+        assert_eq!(Amount::MAX.checked_add(a), None);
+        assert_eq!(a.checked_sub(Amount::from_units(11)), None);
+        assert_eq!(Amount::MAX.checked_mul(2), None);
+        assert_eq!(Amount::checked_sum([a, a, a]), Some(Amount::from_units(30)));
+        assert_eq!(Amount::checked_sum([a, Amount::MAX]), None);
+    }
+
+    #[test]
+    fn decimal_display() {
+        assert_eq!(Amount::from_units(150).display_decimal(2).to_string(), "1.50");
+        assert_eq!(Amount::from_units(5).display_decimal(3).to_string(), "0.005");
+        assert_eq!(Amount::from_units(0).display_decimal(1).to_string(), "0.0");
+        assert_eq!(Amount::from_units(42).display_decimal(0).to_string(), "42");
+        assert_eq!(Amount::MAX.display_decimal(19).to_string(), "1.8446744073709551615");
+    }
+
+    #[test]
+    fn decimal_parsing() {
+        assert_eq!(Amount::from_decimal("1.5", 2).unwrap(), Amount::from_units(150));
+        assert_eq!(Amount::from_decimal("0.005", 3).unwrap(), Amount::from_units(5));
+        assert_eq!(Amount::from_decimal("7", 0).unwrap(), Amount::from_units(7));
+        assert_eq!(Amount::from_decimal("7.", 2).unwrap(), Amount::from_units(700));
+
+        assert!(Amount::from_decimal("1.005", 2).is_err());
+        assert!(Amount::from_decimal(".5", 2).is_err());
+        assert!(Amount::from_decimal("-1", 2).is_err());
+        assert!(Amount::from_decimal("1e3", 2).is_err());
+        assert!(Amount::from_decimal("18446744073709551616", 0).is_err());
+        assert!(Amount::from_decimal("1844674407370955161.6", 1).is_err());
+        assert!(Amount::from_decimal("1", 20).is_err());
+
+        let max = Amount::MAX.display_decimal(19).to_string();
+        assert_eq!(Amount::from_decimal(&max, 19).unwrap(), Amount::MAX);
+    }
+
+    #[test]
+    fn serde_as_number() {
+        let json = serde_json::to_string(&Amount::from_units(21)).unwrap();
+        assert_eq!(json, "21");
+        assert_eq!(serde_json::from_str::<Amount>(&json).unwrap(), Amount::from_units(21));
+    }
+}
@@ -0,0 +1,504 @@
+//! Assembly of the Bitcoin transaction anchoring asset commitments.
+//!
+//! Every anchor output is a P2TR output whose key is its internal key
+//! tweaked with the root of the asset commitment it carries. The builder
+//! funds them from the given inputs, adds the change output when it isn't
+//! dust and returns the unsigned PSBT along with which output carries which
+//! commitment.
+//!
+//! Inputs spending previous anchor outputs carry their [`InputAnchor`] in a
+//! proprietary PSBT field, letting co-signers check with
+//! [`verify_input_anchors`] that they spend the claimed commitments.
+//! [`AnchorTx::finalize`] runs the same check before finalizing. It only
+//! binds the output to a commitment root, whether that root commits to the
+//! spent assets is for the inclusion proofs to show.
+
+use anyhow::{bail, Context};
+use bitcoin::{
+    Amount, FeeRate, OutPoint, Psbt, ScriptBuf, Sequence, TapNodeHash, Transaction, TxIn, TxOut, Weight,
+    absolute::LockTime,
+    hashes::Hash,
+    psbt::raw::ProprietaryKey,
+    secp256k1::{Secp256k1, Verification, XOnlyPublicKey},
+    transaction::Version,
+};
+
+use crate::{script_key::tweak_script_key, witness::Witness};
+
+/// Amount of the anchor outputs unless set otherwise, above the dust limit
+/// of every standard output type.
+pub const DEFAULT_ANCHOR_AMOUNT: Amount = Amount::from_sat(1_000);
+
+/// Dust limit of P2TR outputs at the default relay fee.
+pub const DEFAULT_DUST_LIMIT: Amount = Amount::from_sat(330);
+
+/// Prefix of the proprietary PSBT fields of this crate.
+pub const PROPRIETARY_PREFIX: &[u8] = b"taproot-assets";
+
+/// Proprietary subtype of the [`InputAnchor`] of an input.
+pub const INPUT_ANCHOR_SUBTYPE: u8 = 0x00;
+
+/// Segwit marker and flag bytes, only counted once any input has a witness.
+const SEGWIT_HEADER_WEIGHT: Weight = Weight::from_wu(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputOrder {
+    /// Anchors in the order they were added, change last.
+    #[default]
+    Insertion,
+
+    /// Inputs and outputs sorted following BIP-69.
+    Bip69,
+}
+
+struct Anchor {
+    internal_key: XOnlyPublicKey,
+    commitment_root: TapNodeHash,
+    amount: Option<Amount>,
+}
+
+/// Commitment of the previous anchor output spent by an input.
+///
+/// Only the commitment root is carried, not the assets under it: an anchor
+/// matching its output says nothing about which assets the root commits to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputAnchor {
+    pub internal_key: XOnlyPublicKey,
+    pub commitment_root: TapNodeHash,
+
+    /// Other branch of the tapscript tree, when the commitment isn't its only
+    /// leaf.
+    pub sibling: Option<TapNodeHash>,
+}
+
+impl InputAnchor {
+    pub fn proprietary_key() -> ProprietaryKey {
+        ProprietaryKey { prefix: PROPRIETARY_PREFIX.to_vec(), subtype: INPUT_ANCHOR_SUBTYPE, key: Vec::new() }
+    }
+
+    /// Root of the tapscript tree the internal key is tweaked with.
+    pub fn merkle_root(&self) -> TapNodeHash {
+        match self.sibling {
+            Some(sibling) => TapNodeHash::from_node_hashes(self.commitment_root, sibling),
+            None => self.commitment_root,
+        }
+    }
+
+    pub fn script_pubkey<C: Verification>(&self, secp: &Secp256k1<C>) -> ScriptBuf {
+        ScriptBuf::new_p2tr_tweaked(tweak_script_key(secp, self.internal_key, Some(self.merkle_root())))
+    }
+
+    /// Internal key, commitment root, then the sibling if any.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(96);
+        bytes.extend_from_slice(&self.internal_key.serialize());
+        bytes.extend_from_slice(self.commitment_root.as_byte_array());
+        if let Some(sibling) = self.sibling {
+            bytes.extend_from_slice(sibling.as_byte_array());
+        }
+        bytes
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> anyhow::Result<Self> {
+        let hash = |b: &[u8]| -> anyhow::Result<TapNodeHash> { Ok(TapNodeHash::from_byte_array(b.try_into()?)) };
+
+        let sibling = match bytes.len() {
+            64 => None,
+            96 => Some(hash(&bytes[64..])?),
+            len => bail!("Invalid input anchor length {}", len),
+        };
+
+        Ok(InputAnchor {
+            internal_key: XOnlyPublicKey::from_slice(&bytes[..32]).map_err(anyhow::Error::msg)?,
+            commitment_root: hash(&bytes[32..64])?,
+            sibling,
+        })
+    }
+}
+
+/// Checks that every input carrying an [`InputAnchor`] spends the output
+/// committing to it, returning the number of inputs checked.
+///
+/// Asset inclusion is NOT verified: the check only ties each input to its
+/// commitment root, the assets spent still have to be proven included under
+/// that root.
+pub fn verify_input_anchors<C: Verification>(secp: &Secp256k1<C>, psbt: &Psbt) -> anyhow::Result<usize> {
+    let key = InputAnchor::proprietary_key();
+    let mut checked = 0;
+
+    for (idx, input) in psbt.inputs.iter().enumerate() {
+        let Some(value) = input.proprietary.get(&key) else {
+            continue;
+        };
+        let anchor = InputAnchor::from_slice(value).with_context(|| format!("Invalid anchor of input {}", idx))?;
+        let Some(prev_out) = &input.witness_utxo else {
+            bail!("Input {} has an anchor but no witness utxo", idx);
+        };
+
+        if prev_out.script_pubkey != anchor.script_pubkey(secp) {
+            bail!("Input {} doesn't spend the output of its anchor", idx);
+        }
+        checked += 1;
+    }
+
+    Ok(checked)
+}
+
+/// Unsigned anchor transaction.
+#[derive(Debug, Clone)]
+pub struct AnchorTx {
+    pub psbt: Psbt,
+
+    /// Output index and commitment root of every anchor output.
+    pub commitments: Vec<(usize, TapNodeHash)>,
+
+    /// Index of the change output, if any.
+    pub change: Option<usize>,
+}
+
+impl AnchorTx {
+    /// Finalizer role: checks the input anchors, then turns the key path
+    /// signature of every input into its witness.
+    pub fn finalize<C: Verification>(&mut self, secp: &Secp256k1<C>) -> anyhow::Result<()> {
+        verify_input_anchors(secp, &self.psbt)?;
+
+        if let Some(idx) = self.psbt.inputs.iter().position(|input| input.final_script_witness.is_none() && input.tap_key_sig.is_none()) {
+            bail!("Input {} isn't signed", idx);
+        }
+
+        for input in &mut self.psbt.inputs {
+            if let Some(signature) = input.tap_key_sig.take() {
+                input.final_script_witness = Some(bitcoin::Witness::p2tr_key_spend(&signature));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct AnchorTxBuilder {
+    inputs: Vec<(OutPoint, TxOut)>,
+    input_anchors: Vec<(OutPoint, InputAnchor)>,
+    anchors: Vec<Anchor>,
+    change_script: Option<ScriptBuf>,
+    fee_rate: FeeRate,
+    anchor_amount: Amount,
+    dust_limit: Amount,
+    order: OutputOrder,
+}
+
+impl AnchorTxBuilder {
+    pub fn new(fee_rate: FeeRate) -> Self {
+        AnchorTxBuilder {
+            inputs: Vec::new(),
+            input_anchors: Vec::new(),
+            anchors: Vec::new(),
+            change_script: None,
+            fee_rate,
+            anchor_amount: DEFAULT_ANCHOR_AMOUNT,
+            dust_limit: DEFAULT_DUST_LIMIT,
+            order: OutputOrder::default(),
+        }
+    }
+
+    /// Spends `outpoint`, assumed to be a P2TR output spent by key path for
+    /// the fee estimation.
+    pub fn input(mut self, outpoint: OutPoint, prev_out: TxOut) -> Self {
+        self.inputs.push((outpoint, prev_out));
+        self
+    }
+
+    /// Spends the previous anchor output `outpoint`, recording its commitment
+    /// in the PSBT.
+    pub fn asset_input(mut self, outpoint: OutPoint, prev_out: TxOut, anchor: InputAnchor) -> Self {
+        self.input_anchors.push((outpoint, anchor));
+        self.input(outpoint, prev_out)
+    }
+
+    /// Adds an anchor output of the default anchor amount.
+    pub fn anchor(mut self, internal_key: XOnlyPublicKey, commitment_root: TapNodeHash) -> Self {
+        self.anchors.push(Anchor { internal_key, commitment_root, amount: None });
+        self
+    }
+
+    pub fn anchor_with_amount(mut self, internal_key: XOnlyPublicKey, commitment_root: TapNodeHash, amount: Amount) -> Self {
+        self.anchors.push(Anchor { internal_key, commitment_root, amount: Some(amount) });
+        self
+    }
+
+    pub fn change_script(mut self, script: ScriptBuf) -> Self {
+        self.change_script = Some(script);
+        self
+    }
+
+    pub fn anchor_amount(mut self, amount: Amount) -> Self {
+        self.anchor_amount = amount;
+        self
+    }
+
+    pub fn dust_limit(mut self, amount: Amount) -> Self {
+        self.dust_limit = amount;
+        self
+    }
+
+    pub fn order(mut self, order: OutputOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn build<C: Verification>(&self, secp: &Secp256k1<C>) -> anyhow::Result<AnchorTx> {
+        if self.inputs.is_empty() || self.anchors.is_empty() {
+            bail!("Anchor transaction needs at least an input and an anchor");
+        }
+
+        let mut inputs = self.inputs.clone();
+        let mut outputs = Vec::with_capacity(self.anchors.len() + 1);
+        for anchor in &self.anchors {
+            let amount = anchor.amount.unwrap_or(self.anchor_amount);
+            if amount < self.dust_limit {
+                bail!("Anchor output of {} is below the dust limit of {}", amount, self.dust_limit);
+            }
+
+            let output_key = tweak_script_key(secp, anchor.internal_key, Some(anchor.commitment_root));
+            outputs.push((TxOut { value: amount, script_pubkey: ScriptBuf::new_p2tr_tweaked(output_key) }, Some(anchor)));
+        }
+
+        let input_value = inputs.iter().try_fold(Amount::ZERO, |sum, (_, prev_out)| sum.checked_add(prev_out.value)).context("Input value overflow")?;
+        let output_value = outputs.iter().try_fold(Amount::ZERO, |sum, (out, _)| sum.checked_add(out.value)).context("Output value overflow")?;
+
+        if let Some(script) = &self.change_script {
+            let change = TxOut { value: Amount::ZERO, script_pubkey: script.clone() };
+            let fee = self.fee(&inputs, &outputs, Some(&change))?;
+
+            let change_value = input_value.checked_sub(output_value).and_then(|left| left.checked_sub(fee));
+            if let Some(value) = change_value
+                && value >= self.dust_limit
+            {
+                outputs.push((TxOut { value, ..change }, None));
+            }
+        }
+
+        let change_added = outputs.len() > self.anchors.len();
+        if !change_added {
+            let fee = self.fee(&inputs, &outputs, None)?;
+            let Some(left) = input_value.checked_sub(output_value).and_then(|left| left.checked_sub(fee)) else {
+                bail!("Inputs of {} can't pay outputs of {} and a fee of {}", input_value, output_value, fee);
+            };
+
+            if self.change_script.is_none() && left >= self.dust_limit {
+                bail!("No change script to send the {} left to", left);
+            }
+        }
+
+        if self.order == OutputOrder::Bip69 {
+            // Txids compare in their displayed byte order, the reverse of
+            // the internal one.
+            inputs.sort_by_key(|(outpoint, _)| {
+                let mut txid = outpoint.txid.to_byte_array();
+                txid.reverse();
+                (txid, outpoint.vout)
+            });
+            outputs.sort_by(|(a, _), (b, _)| (a.value, a.script_pubkey.as_bytes()).cmp(&(b.value, b.script_pubkey.as_bytes())));
+        }
+
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: inputs.iter().map(|(outpoint, _)| tx_in(*outpoint)).collect(),
+            output: outputs.iter().map(|(out, _)| out.clone()).collect(),
+        };
+
+        let mut psbt = Psbt::from_unsigned_tx(tx).map_err(|e| anyhow::anyhow!("Invalid anchor transaction: {}", e))?;
+        for (input, (outpoint, prev_out)) in psbt.inputs.iter_mut().zip(&inputs) {
+            input.witness_utxo = Some(prev_out.clone());
+
+            if let Some((_, anchor)) = self.input_anchors.iter().find(|(spent, _)| spent == outpoint) {
+                input.proprietary.insert(InputAnchor::proprietary_key(), anchor.serialize());
+            }
+        }
+
+        let mut commitments = Vec::with_capacity(self.anchors.len());
+        let mut change = None;
+        for (idx, (_, anchor)) in outputs.iter().enumerate() {
+            match anchor {
+                Some(anchor) => {
+                    psbt.outputs[idx].tap_internal_key = Some(anchor.internal_key);
+                    commitments.push((idx, anchor.commitment_root));
+                },
+                None => change = Some(idx),
+            }
+        }
+
+        Ok(AnchorTx { psbt, commitments, change })
+    }
+
+    /// Fee of the transaction with the given outputs, every input being a
+    /// key spend.
+    fn fee(&self, inputs: &[(OutPoint, TxOut)], outputs: &[(TxOut, Option<&Anchor>)], change: Option<&TxOut>) -> anyhow::Result<Amount> {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: inputs.iter().map(|(outpoint, _)| tx_in(*outpoint)).collect(),
+            output: outputs.iter().map(|(out, _)| out).chain(change).cloned().collect(),
+        };
+
+        let witness_weight = Witness::key_spend_estimate().weight() * inputs.len() as u64;
+        self.fee_rate.fee_wu(tx.weight() + SEGWIT_HEADER_WEIGHT + witness_weight).context("Fee overflow")
+    }
+}
+
+fn tx_in(previous_output: OutPoint) -> TxIn {
+    TxIn { previous_output, script_sig: ScriptBuf::new(), sequence: Sequence::ENABLE_RBF_NO_LOCKTIME, witness: bitcoin::Witness::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        Amount, FeeRate, OutPoint, ScriptBuf, TapNodeHash, TapSighashType, TxOut, Txid,
+        hashes::Hash,
+        secp256k1::{Secp256k1, schnorr::Signature},
+    };
+
+    use super::{AnchorTxBuilder, InputAnchor, OutputOrder, verify_input_anchors};
+    use crate::script_key::{nums_key, tweak_script_key};
+
+    fn input(b: u8, sats: u64) -> (OutPoint, TxOut) {
+        (OutPoint::new(Txid::from_byte_array([b; 32]), 0), TxOut { value: Amount::from_sat(sats), script_pubkey: ScriptBuf::new_op_return([b]) })
+    }
+
+    #[test]
+    fn build_anchor_tx() {
+        let secp = Secp256k1::verification_only();
+        let fee_rate = FeeRate::from_sat_per_vb(10).unwrap();
+        let (a, b) = (TapNodeHash::from_byte_array([1; 32]), TapNodeHash::from_byte_array([2; 32]));
+        let change_script = ScriptBuf::new_op_return([9]);
+
+        let (outpoint, prev_out) = input(1, 100_000);
+        let builder = AnchorTxBuilder::new(fee_rate)
+            .input(outpoint, prev_out)
+            .anchor(nums_key(), a)
+            .anchor_with_amount(nums_key(), b, Amount::from_sat(500))
+            .change_script(change_script.clone());
+
+        let anchor_tx = builder.build(&secp).unwrap();
+        let tx = &anchor_tx.psbt.unsigned_tx;
+        assert_eq!(tx.output.len(), 3);
+        assert_eq!(anchor_tx.commitments, vec![(0, a), (1, b)]);
+        assert_eq!(anchor_tx.change, Some(2));
+        assert_eq!(tx.output[1].value, Amount::from_sat(500));
+        assert_eq!(tx.output[0].script_pubkey, ScriptBuf::new_p2tr_tweaked(tweak_script_key(&secp, nums_key(), Some(a))));
+        assert_eq!(anchor_tx.psbt.outputs[0].tap_internal_key, Some(nums_key()));
+
+        // The fee covers the signed transaction.
+        let fee = anchor_tx.psbt.fee().unwrap();
+        let signed_vsize = (tx.weight().to_wu() + 2 + 66).div_ceil(4);
+        assert_eq!(fee, Amount::from_sat(signed_vsize * 10));
+
+        // Sorted outputs keep their commitments.
+        let sorted = builder.order(OutputOrder::Bip69).build(&secp).unwrap();
+        assert_eq!(sorted.commitments[0], (0, b));
+        assert_eq!(sorted.psbt.unsigned_tx.output[sorted.commitments[1].0].script_pubkey, tx.output[0].script_pubkey);
+    }
+
+    #[test]
+    fn bip69_input_vector() {
+        let secp = Secp256k1::verification_only();
+
+        // Inputs of the first BIP-69 example, in their sorted order.
+        let sorted: Vec<OutPoint> = [
+            ("0e53ec5dfb2cb8a71fec32dc9a634a35b7e24799295ddd5278217822e0b31f57", 0),
+            ("26aa6e6d8b9e49bb0630aac301db6757c02e3619feb4ee0eea81eb1672947024", 1),
+            ("28e0fdd185542f2c6ea19030b0796051e7772b6026dd5ddccd7a2f93b73e6fc2", 0),
+            ("381de9b9ae1a94d9c17f6a08ef9d341a5ce29e2e60c36a52d333ff6203e58d5d", 1),
+            ("3b8b2f8efceb60ba78ca8bba206a137f14cb5ea4035e761ee204302d46b98de2", 0),
+            ("402b2c02411720bf409eff60d05adad684f135838962823f3614cc657dd7bc0a", 1),
+            ("54ffff182965ed0957dba1239c27164ace5a73c9b62a660c74b7b7f15ff61e7a", 1),
+            ("643e5f4e66373a57251fb173151e838ccd27d279aca882997e005016bb53d5aa", 0),
+            ("6c1d56f31b2de4bfc6aaea28396b333102b1f600da9c6d6149e96ca43f1102b1", 1),
+            ("7a1de137cbafb5c70405455c49c5104ca3057a1f1243e6563bb9245c9c88c191", 0),
+            ("7d037ceb2ee0dc03e82f17be7935d238b35d1deabf953a892a4507bfbeeb3ba4", 1),
+            ("a5e899dddb28776ea9ddac0a502316d53a4a3fca607c72f66c470e0412e34086", 0),
+            ("b4112b8f900a7ca0c8b0e7c4dfad35c6be5f6be46b3458974988e1cdb2fa61b8", 0),
+            ("bafd65e3c7f3f9fdfdc1ddb026131b278c3be1af90a4a6ffa78c4658f9ec0c85", 0),
+            ("de0411a1e97484a2804ff1dbde260ac19de841bebad1880c782941aca883b4e9", 1),
+            ("f0a130a84912d03c1d284974f563c5949ac13f8342b8112edff52971599e6a45", 0),
+            ("f320832a9d2e2452af63154bc687493484a0e7745ebd3aaf9ca19eb80834ad60", 0),
+        ]
+        .iter()
+        .map(|(txid, vout)| OutPoint::new(txid.parse().unwrap(), *vout))
+        .collect();
+
+        let mut builder = AnchorTxBuilder::new(FeeRate::from_sat_per_vb(1).unwrap())
+            .anchor(nums_key(), TapNodeHash::from_byte_array([1; 32]))
+            .change_script(ScriptBuf::new_op_return([9]))
+            .order(OutputOrder::Bip69);
+        for outpoint in sorted.iter().rev() {
+            builder = builder.input(*outpoint, TxOut { value: Amount::from_sat(10_000), script_pubkey: ScriptBuf::new_op_return([1]) });
+        }
+
+        let tx = builder.build(&secp).unwrap().psbt.unsigned_tx;
+        assert_eq!(tx.input.iter().map(|input| input.previous_output).collect::<Vec<_>>(), sorted);
+    }
+
+    #[test]
+    fn change_and_dust() {
+        let secp = Secp256k1::verification_only();
+        let fee_rate = FeeRate::from_sat_per_vb(1).unwrap();
+        let root = TapNodeHash::from_byte_array([1; 32]);
+
+        // Change below the dust limit goes to the fee.
+        let (outpoint, prev_out) = input(1, 1_300);
+        let anchor_tx = AnchorTxBuilder::new(fee_rate).input(outpoint, prev_out.clone()).anchor(nums_key(), root).change_script(ScriptBuf::new()).build(&secp).unwrap();
+        assert_eq!(anchor_tx.change, None);
+        assert_eq!(anchor_tx.psbt.unsigned_tx.output.len(), 1);
+
+        // Not enough to pay the fee.
+        assert!(AnchorTxBuilder::new(fee_rate).input(outpoint, prev_out.clone()).anchor_amount(Amount::from_sat(1_290)).anchor(nums_key(), root).build(&secp).is_err());
+
+        // Dust anchors and change without a change script.
+        assert!(AnchorTxBuilder::new(fee_rate).input(outpoint, prev_out.clone()).anchor_with_amount(nums_key(), root, Amount::from_sat(100)).build(&secp).is_err());
+        let (outpoint, prev_out) = input(2, 100_000);
+        assert!(AnchorTxBuilder::new(fee_rate).input(outpoint, prev_out).anchor(nums_key(), root).build(&secp).is_err());
+    }
+
+    #[test]
+    fn input_anchors() {
+        let secp = Secp256k1::verification_only();
+        let fee_rate = FeeRate::from_sat_per_vb(1).unwrap();
+        let anchor = InputAnchor { internal_key: nums_key(), commitment_root: TapNodeHash::from_byte_array([1; 32]), sibling: Some(TapNodeHash::from_byte_array([2; 32])) };
+        assert_eq!(InputAnchor::from_slice(&anchor.serialize()).unwrap(), anchor);
+        let bare = InputAnchor { sibling: None, ..anchor };
+        assert_eq!(InputAnchor::from_slice(&bare.serialize()).unwrap(), bare);
+        assert!(InputAnchor::from_slice(&[0; 65]).is_err());
+
+        let (outpoint, _) = input(1, 0);
+        let prev_out = TxOut { value: Amount::from_sat(1_000), script_pubkey: anchor.script_pubkey(&secp) };
+        let (fee_outpoint, fee_prev_out) = input(2, 10_000);
+
+        let mut anchor_tx = AnchorTxBuilder::new(fee_rate)
+            .input(fee_outpoint, fee_prev_out)
+            .asset_input(outpoint, prev_out, anchor)
+            .anchor(nums_key(), TapNodeHash::from_byte_array([3; 32]))
+            .change_script(ScriptBuf::new_op_return([9]))
+            .order(OutputOrder::Bip69)
+            .build(&secp)
+            .unwrap();
+        assert_eq!(verify_input_anchors(&secp, &anchor_tx.psbt).unwrap(), 1);
+
+        assert!(anchor_tx.clone().finalize(&secp).is_err());
+        let signature = bitcoin::taproot::Signature { signature: Signature::from_slice(&[1; 64]).unwrap(), sighash_type: TapSighashType::Default };
+        for input in &mut anchor_tx.psbt.inputs {
+            input.tap_key_sig = Some(signature);
+        }
+
+        let mut finalized = anchor_tx.clone();
+        finalized.finalize(&secp).unwrap();
+        assert!(finalized.psbt.inputs.iter().all(|input| input.final_script_witness.as_ref().is_some_and(|witness| witness.len() == 1)));
+
+        // An anchor the spent output doesn't commit to.
+        let idx = anchor_tx.psbt.unsigned_tx.input.iter().position(|input| input.previous_output == outpoint).unwrap();
+        anchor_tx.psbt.inputs[idx].proprietary.insert(InputAnchor::proprietary_key(), bare.serialize());
+        assert!(verify_input_anchors(&secp, &anchor_tx.psbt).is_err());
+        assert!(anchor_tx.finalize(&secp).is_err());
+    }
+}
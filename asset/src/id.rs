@@ -0,0 +1,154 @@
+use std::{fmt::Display, str::FromStr};
+
+use bitcoin::{
+    hashes::{Hash, sha256},
+    hex::{DisplayHex, FromHex},
+};
+use mssmt::tree::NodeHash;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Identifies an asset, it is the sha256 of the asset genesis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AssetId([u8; 32]);
+
+impl AssetId {
+    pub fn new(b: [u8; 32]) -> Self {
+        AssetId(b)
+    }
+
+    pub fn from_slice(b: &[u8]) -> Result<Self, String> {
+        let res = <[u8; 32]>::try_from(b).map_err(|_| format!("Invalid slice length {}", b.len()))?;
+
+        Ok(AssetId(res))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn to_byte_array(self) -> [u8; 32] {
+        self.0
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.as_hex().to_string()
+    }
+}
+
+impl FromStr for AssetId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 {
+            return Err("Invalid string length".to_string());
+        }
+        let res = <[u8; 32]>::from_hex(s).map_err(|e| format!("Hex to array error {:?}", e))?;
+
+        Ok(AssetId(res))
+    }
+}
+
+impl From<[u8; 32]> for AssetId {
+    fn from(b: [u8; 32]) -> Self {
+        AssetId(b)
+    }
+}
+
+impl From<AssetId> for [u8; 32] {
+    fn from(id: AssetId) -> Self {
+        id.0
+    }
+}
+
+impl From<sha256::Hash> for AssetId {
+    fn from(hash: sha256::Hash) -> Self {
+        AssetId(hash.to_byte_array())
+    }
+}
+
+impl From<AssetId> for sha256::Hash {
+    fn from(id: AssetId) -> Self {
+        sha256::Hash::from_byte_array(id.0)
+    }
+}
+
+/// Asset ids key the assets of a commitment tree.
+impl From<AssetId> for NodeHash {
+    fn from(id: AssetId) -> Self {
+        NodeHash::new(id.0)
+    }
+}
+
+impl From<NodeHash> for AssetId {
+    fn from(hash: NodeHash) -> Self {
+        AssetId(hash.to_byte_array())
+    }
+}
+
+impl TryFrom<&[u8]> for AssetId {
+    type Error = String;
+
+    fn try_from(b: &[u8]) -> Result<Self, Self::Error> {
+        Self::from_slice(b)
+    }
+}
+
+impl Display for AssetId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+/// Serialized as a hex string.
+impl Serialize for AssetId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for AssetId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use bitcoin::hashes::{Hash, sha256};
+    use mssmt::tree::NodeHash;
+
+    use super::AssetId;
+
+    #[test]
+    fn constructors() {
+        let id = AssetId::from_slice(&[7; 32]).unwrap();
+        assert_eq!(id, AssetId::new([7; 32]));
+        assert_eq!(id.to_hex(), "07".repeat(32));
+        assert_eq!(id.to_hex().parse::<AssetId>().unwrap(), id);
+
+        assert!(AssetId::from_slice(&[7; 31]).is_err());
+        assert!(AssetId::from_slice(&[7; 33]).is_err());
+
+        let genesis = sha256::Hash::hash(b"genesis");
+        assert_eq!(sha256::Hash::from(AssetId::from(genesis)), genesis);
+        assert_eq!(AssetId::from(NodeHash::from(id)), id);
+        assert_eq!(NodeHash::from(id).as_bytes(), id.as_bytes());
+        assert_eq!(AssetId::try_from(&[7u8; 32][..]), Ok(id));
+
+        let ids: HashSet<AssetId> = [id, id, AssetId::new([8; 32])].into_iter().collect();
+        assert_eq!(ids.len(), 2);
+    }
+
+    #[test]
+    fn serde_as_hex() {
+        let id = AssetId::new([0xab; 32]);
+        let json = serde_json::to_string(&id).unwrap();
+
+        assert_eq!(json, format!("\"{}\"", "ab".repeat(32)));
+        assert_eq!(serde_json::from_str::<AssetId>(&json).unwrap(), id);
+        assert!(serde_json::from_str::<AssetId>("\"abcd\"").is_err());
+    }
+}
@@ -0,0 +1,11 @@
+pub mod amount;
+pub mod anchor;
+pub mod id;
+pub mod script_key;
+pub mod signer;
+pub mod stealth;
+pub mod vesting;
+pub mod vpsbt;
+pub mod wallet;
+pub mod watch;
+pub mod witness;
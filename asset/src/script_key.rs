@@ -0,0 +1,155 @@
+//! Script key helpers: the NUMS key, BIP-341 tweaks of internal keys and
+//! the provenance of script keys.
+
+use bitcoin::{
+    TapNodeHash,
+    key::{TapTweak, TweakedPublicKey},
+    secp256k1::{Secp256k1, Verification, XOnlyPublicKey},
+};
+
+/// x coordinate of the NUMS key of Taproot Assets, the internal key tapd
+/// gives script keys that must only be spendable through their script tree.
+/// Nobody knows its discrete log.
+const NUMS_X: [u8; 32] = [
+    0x7c, 0x79, 0xb9, 0xb2, 0x6e, 0x46, 0x38, 0x95, 0xee, 0xf5, 0x67, 0x9d, 0x85, 0x58, 0x94, 0x2c,
+    0x86, 0xc4, 0xad, 0x22, 0x33, 0xad, 0xef, 0x01, 0xbc, 0x3e, 0x6d, 0x54, 0x0b, 0x36, 0x53, 0xfe,
+];
+
+/// x coordinate of the NUMS point H from BIP-341, the sha256 of the
+/// uncompressed secp256k1 generator.
+const BIP341_NUMS_X: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+pub fn nums_key() -> XOnlyPublicKey {
+    XOnlyPublicKey::from_slice(&NUMS_X).expect("NUMS point is on the curve")
+}
+
+pub fn bip341_nums_key() -> XOnlyPublicKey {
+    XOnlyPublicKey::from_slice(&BIP341_NUMS_X).expect("NUMS point is on the curve")
+}
+
+/// Tweaks `internal_key` with `merkle_root` following BIP-341, a missing root
+/// giving the BIP-86 key-spend only key.
+pub fn tweak_script_key<C: Verification>(
+    secp: &Secp256k1<C>,
+    internal_key: XOnlyPublicKey,
+    merkle_root: Option<TapNodeHash>,
+) -> TweakedPublicKey {
+    internal_key.tap_tweak(secp, merkle_root).0
+}
+
+/// Checks that `script_key` is the tweak of `internal_key` with `merkle_root`.
+pub fn verify_script_key<C: Verification>(
+    secp: &Secp256k1<C>,
+    script_key: &XOnlyPublicKey,
+    internal_key: XOnlyPublicKey,
+    merkle_root: Option<TapNodeHash>,
+) -> bool {
+    tweak_script_key(secp, internal_key, merkle_root).to_x_only_public_key() == *script_key
+}
+
+/// Script key which can only be spent through `merkle_root`, since its
+/// internal key is [`nums_key`].
+pub fn nums_script_key<C: Verification>(secp: &Secp256k1<C>, merkle_root: TapNodeHash) -> TweakedPublicKey {
+    tweak_script_key(secp, nums_key(), Some(merkle_root))
+}
+
+/// Where a script key comes from, telling whether the assets it holds count
+/// towards the local balance.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ScriptKeyType {
+    /// Never declared, assets held by it are not assumed to be ours.
+    #[default]
+    Unknown,
+
+    /// Derived by the local signer, the only spendable kind.
+    Local,
+
+    /// Declared by a counterparty, e.g. the script key of an address
+    /// assets were sent to.
+    Declared,
+
+    /// Provably unspendable key the assets were burnt to.
+    Burn,
+
+    /// [`nums_key`] itself, held by the zero value tombstones of full
+    /// value transfers.
+    Nums,
+}
+
+impl ScriptKeyType {
+    pub fn is_spendable(self) -> bool {
+        self == ScriptKeyType::Local
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            ScriptKeyType::Unknown => 0,
+            ScriptKeyType::Local => 1,
+            ScriptKeyType::Declared => 2,
+            ScriptKeyType::Burn => 3,
+            ScriptKeyType::Nums => 4,
+        }
+    }
+
+    pub fn from_u8(b: u8) -> Option<Self> {
+        match b {
+            0 => Some(ScriptKeyType::Unknown),
+            1 => Some(ScriptKeyType::Local),
+            2 => Some(ScriptKeyType::Declared),
+            3 => Some(ScriptKeyType::Burn),
+            4 => Some(ScriptKeyType::Nums),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        ScriptBuf, TapNodeHash,
+        secp256k1::{Parity, Secp256k1, XOnlyPublicKey},
+        taproot::{LeafVersion, TapLeafHash},
+    };
+
+    use super::{bip341_nums_key, nums_key, nums_script_key, tweak_script_key, verify_script_key};
+
+    #[test]
+    fn nums_keys() {
+        // Compressed form tapd uses, with an even y coordinate.
+        assert_eq!(nums_key().public_key(Parity::Even).to_string(), "027c79b9b26e463895eef5679d8558942c86c4ad2233adef01bc3e6d540b3653fe");
+        assert_eq!(bip341_nums_key().to_string(), "50929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0");
+    }
+
+    #[test]
+    fn bip341_key_path_vector() {
+        let secp = Secp256k1::verification_only();
+
+        // First wallet test vector of BIP-341, no script tree.
+        let internal = XOnlyPublicKey::from_str("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d").unwrap();
+        let tweaked = XOnlyPublicKey::from_str("53a1f6e454df1aa2776a2814a721372d6258050de330b3c6d10ee8f4e0dda343").unwrap();
+
+        assert_eq!(tweak_script_key(&secp, internal, None).to_x_only_public_key(), tweaked);
+        assert!(verify_script_key(&secp, &tweaked, internal, None));
+    }
+
+    #[test]
+    fn verify_against_root() {
+        let secp = Secp256k1::verification_only();
+
+        let leaf = TapLeafHash::from_script(&ScriptBuf::from_bytes(vec![0x51]), LeafVersion::TapScript);
+        let root = TapNodeHash::from(leaf);
+        let other = TapNodeHash::from(TapLeafHash::from_script(&ScriptBuf::new(), LeafVersion::TapScript));
+
+        let script_key = nums_script_key(&secp, root).to_x_only_public_key();
+
+        assert!(verify_script_key(&secp, &script_key, nums_key(), Some(root)));
+        assert!(!verify_script_key(&secp, &script_key, nums_key(), Some(other)));
+        assert!(!verify_script_key(&secp, &script_key, nums_key(), None));
+        assert_ne!(script_key, nums_key());
+    }
+}
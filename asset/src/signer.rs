@@ -0,0 +1,154 @@
+//! Signing behind an interface, so the keys can live in another process
+//! than the wallet building the transfers.
+//!
+//! [`Signer`] is what a remote signer serves, [`KeySigner`] implements it
+//! from a local master key. Script keys are the BIP-86 tweak of the keys
+//! derived at the requested paths.
+
+use anyhow::bail;
+use bitcoin::{
+    NetworkKind,
+    bip32::{DerivationPath, Fingerprint, Xpriv, Xpub},
+    key::TapTweak,
+    secp256k1::{All, Message, Secp256k1, XOnlyPublicKey, schnorr::Signature},
+};
+
+use crate::{script_key::tweak_script_key, vpsbt::VPsbt};
+
+pub trait Signer {
+    /// Fingerprint of the master key, matched against the derivation hints
+    /// of virtual inputs.
+    fn fingerprint(&self) -> Fingerprint;
+
+    fn xpub(&self, path: &DerivationPath) -> anyhow::Result<Xpub>;
+
+    fn derive_script_key(&self, path: &DerivationPath) -> anyhow::Result<XOnlyPublicKey>;
+
+    /// Signs `sighash` with the script key derived at `path`.
+    fn sign_sighash(&self, path: &DerivationPath, sighash: &Message) -> anyhow::Result<Signature>;
+}
+
+pub struct KeySigner {
+    secp: Secp256k1<All>,
+    master: Xpriv,
+}
+
+impl KeySigner {
+    pub fn new(master: Xpriv) -> Self {
+        KeySigner { secp: Secp256k1::new(), master }
+    }
+
+    pub fn from_seed(network: NetworkKind, seed: &[u8]) -> anyhow::Result<Self> {
+        Ok(Self::new(Xpriv::new_master(network, seed).map_err(anyhow::Error::msg)?))
+    }
+
+    fn derive(&self, path: &DerivationPath) -> anyhow::Result<Xpriv> {
+        self.master.derive_priv(&self.secp, path).map_err(anyhow::Error::msg)
+    }
+}
+
+impl Signer for KeySigner {
+    fn fingerprint(&self) -> Fingerprint {
+        self.master.fingerprint(&self.secp)
+    }
+
+    fn xpub(&self, path: &DerivationPath) -> anyhow::Result<Xpub> {
+        Ok(Xpub::from_priv(&self.secp, &self.derive(path)?))
+    }
+
+    fn derive_script_key(&self, path: &DerivationPath) -> anyhow::Result<XOnlyPublicKey> {
+        let internal = self.derive(path)?.to_keypair(&self.secp).x_only_public_key().0;
+
+        Ok(tweak_script_key(&self.secp, internal, None).to_x_only_public_key())
+    }
+
+    fn sign_sighash(&self, path: &DerivationPath, sighash: &Message) -> anyhow::Result<Signature> {
+        let keypair = self.derive(path)?.to_keypair(&self.secp).tap_tweak(&self.secp, None).to_keypair();
+
+        Ok(self.secp.sign_schnorr_no_aux_rand(sighash, &keypair))
+    }
+}
+
+/// Signer role of a virtual transaction: signs `sighashes[idx]` for every
+/// input whose derivation hint names `signer` and derives its script key,
+/// returning the number of inputs signed.
+pub fn sign_vpsbt<S: Signer>(signer: &S, vpsbt: &mut VPsbt, sighashes: &[Message]) -> anyhow::Result<usize> {
+    if sighashes.len() != vpsbt.inputs().len() {
+        bail!("Got {} sighashes for {} inputs", sighashes.len(), vpsbt.inputs().len());
+    }
+
+    let fingerprint = signer.fingerprint();
+    let mut signed = 0;
+
+    for (idx, sighash) in sighashes.iter().enumerate() {
+        let input = &vpsbt.inputs()[idx];
+        let Some((input_fingerprint, path)) = &input.derivation else {
+            continue;
+        };
+        if *input_fingerprint != fingerprint || input.signature.is_some() {
+            continue;
+        }
+        if signer.derive_script_key(path)? != input.script_key {
+            bail!("Script key of input {} isn't derived at {}", input.prev_out, path);
+        }
+
+        let signature = signer.sign_sighash(path, sighash)?;
+        vpsbt.set_signature(idx, signature)?;
+        signed += 1;
+    }
+
+    Ok(signed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        NetworkKind, OutPoint, Txid,
+        bip32::{DerivationPath, Fingerprint},
+        hashes::Hash,
+        secp256k1::{Message, Secp256k1},
+    };
+
+    use super::{KeySigner, Signer, sign_vpsbt};
+    use crate::{
+        amount::Amount,
+        id::AssetId,
+        script_key::verify_script_key,
+        vpsbt::{VInput, VOutput, VPsbt},
+    };
+
+    #[test]
+    fn sign_virtual_inputs() {
+        let secp = Secp256k1::verification_only();
+        let signer = KeySigner::from_seed(NetworkKind::Test, &[1; 32]).unwrap();
+        let path = DerivationPath::from_str("m/86'/1'/0'/0/0").unwrap();
+
+        let script_key = signer.derive_script_key(&path).unwrap();
+        let xpub = signer.xpub(&path).unwrap();
+        assert!(verify_script_key(&secp, &script_key, xpub.public_key.x_only_public_key().0, None));
+
+        let asset_id = AssetId::new([1; 32]);
+        let mut ours = VInput::new(OutPoint::new(Txid::from_byte_array([1; 32]), 0), asset_id, script_key, Amount::from_units(60));
+        ours.derivation = Some((signer.fingerprint(), path.clone()));
+        let mut theirs = VInput::new(OutPoint::new(Txid::from_byte_array([2; 32]), 0), asset_id, script_key, Amount::from_units(40));
+        theirs.derivation = Some((Fingerprint::from([9; 4]), path.clone()));
+        let mut wrong_hint = ours.clone();
+        wrong_hint.derivation = Some((signer.fingerprint(), DerivationPath::from_str("m/86'/1'/0'/0/1").unwrap()));
+
+        let mut vpsbt = VPsbt::new(vec![ours, theirs.clone()], vec![VOutput::new(Amount::from_units(100), script_key, 0)]).unwrap();
+        let sighashes = [Message::from_digest([1; 32]), Message::from_digest([2; 32])];
+
+        assert!(sign_vpsbt(&signer, &mut vpsbt, &sighashes[..1]).is_err());
+        assert_eq!(sign_vpsbt(&signer, &mut vpsbt, &sighashes).unwrap(), 1);
+        assert!(vpsbt.inputs()[1].signature.is_none());
+
+        let signature = vpsbt.inputs()[0].signature.unwrap();
+        assert!(secp.verify_schnorr(&signature, &sighashes[0], &script_key).is_ok());
+
+        // A hint naming us with a script key we don't derive.
+        let mut wrong = VPsbt::new(vec![wrong_hint, theirs], vpsbt.outputs().to_vec()).unwrap();
+        assert!(sign_vpsbt(&signer, &mut wrong, &sighashes).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+//! One-time script keys derived by ECDH, so payments to the same receiver
+//! can't be linked on chain or in universes.
+//!
+//! The sender picks an ephemeral key `e` and derives the shared secret
+//! `S = e * B` with the receiver key `B`. The one-time internal key is
+//! `P = B + t * G` with `t` the tagged hash of `S`, the script key being its
+//! BIP-86 tweak. The sender publishes `E = e * G` along with the transfer,
+//! from which the receiver computes `S = b * E` and the spending key `b + t`.
+
+use bitcoin::{
+    hashes::{Hash, HashEngine, sha256},
+    key::TweakedPublicKey,
+    secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification, XOnlyPublicKey},
+};
+
+use crate::script_key::tweak_script_key;
+
+const STEALTH_TAG: &[u8] = b"taproot-assets/stealth";
+
+/// What the sender publishes for the receiver to find the payment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyExchange {
+    pub ephemeral_key: PublicKey,
+}
+
+impl KeyExchange {
+    pub fn serialize(&self) -> [u8; 33] {
+        self.ephemeral_key.serialize()
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(KeyExchange { ephemeral_key: PublicKey::from_slice(bytes).map_err(anyhow::Error::msg)? })
+    }
+}
+
+fn shared_tweak(shared: &PublicKey) -> anyhow::Result<Scalar> {
+    let tag = sha256::Hash::hash(STEALTH_TAG);
+
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_byte_array());
+    engine.input(tag.as_byte_array());
+    engine.input(&shared.serialize());
+
+    Scalar::from_be_bytes(sha256::Hash::from_engine(engine).to_byte_array()).map_err(anyhow::Error::msg)
+}
+
+/// Derives the one-time script key paying `receiver`, and the key exchange
+/// record to publish with the payment.
+pub fn stealth_send<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    receiver: &PublicKey,
+    ephemeral: &SecretKey,
+) -> anyhow::Result<(TweakedPublicKey, KeyExchange)> {
+    let shared = receiver.mul_tweak(secp, &Scalar::from(*ephemeral)).map_err(anyhow::Error::msg)?;
+    let one_time = receiver.add_exp_tweak(secp, &shared_tweak(&shared)?).map_err(anyhow::Error::msg)?;
+
+    let script_key = tweak_script_key(secp, one_time.x_only_public_key().0, None);
+
+    Ok((script_key, KeyExchange { ephemeral_key: ephemeral.public_key(secp) }))
+}
+
+/// Checks whether `script_key` pays the owner of `receiver`, returning the
+/// secret of the one-time internal key if it does.
+pub fn stealth_detect<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    receiver: &SecretKey,
+    exchange: &KeyExchange,
+    script_key: &XOnlyPublicKey,
+) -> anyhow::Result<Option<SecretKey>> {
+    let shared = exchange.ephemeral_key.mul_tweak(secp, &Scalar::from(*receiver)).map_err(anyhow::Error::msg)?;
+    let secret = receiver.add_tweak(&shared_tweak(&shared)?).map_err(anyhow::Error::msg)?;
+
+    let derived = tweak_script_key(secp, secret.x_only_public_key(secp).0, None);
+
+    Ok((derived.to_x_only_public_key() == *script_key).then_some(secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::{Secp256k1, SecretKey};
+
+    use super::{KeyExchange, stealth_detect, stealth_send};
+    use crate::script_key::tweak_script_key;
+
+    #[test]
+    fn send_and_detect() {
+        let secp = Secp256k1::new();
+        let receiver = SecretKey::from_slice(&[1; 32]).unwrap();
+        let other = SecretKey::from_slice(&[2; 32]).unwrap();
+
+        let (first, exchange) = stealth_send(&secp, &receiver.public_key(&secp), &SecretKey::from_slice(&[3; 32]).unwrap()).unwrap();
+        let (second, _) = stealth_send(&secp, &receiver.public_key(&secp), &SecretKey::from_slice(&[4; 32]).unwrap()).unwrap();
+        assert_ne!(first, second);
+
+        let exchange = KeyExchange::from_slice(&exchange.serialize()).unwrap();
+        let first = first.to_x_only_public_key();
+
+        let secret = stealth_detect(&secp, &receiver, &exchange, &first).unwrap().unwrap();
+        assert_eq!(tweak_script_key(&secp, secret.x_only_public_key(&secp).0, None).to_x_only_public_key(), first);
+
+        assert!(stealth_detect(&secp, &other, &exchange, &first).unwrap().is_none());
+        assert!(stealth_detect(&secp, &receiver, &exchange, &second.to_x_only_public_key()).unwrap().is_none());
+    }
+}
@@ -0,0 +1,71 @@
+//! Script keys locking assets until a block height, for allocations vesting
+//! on chain.
+
+use bitcoin::{
+    ScriptBuf,
+    absolute::{Height, LockTime},
+    opcodes::all::{OP_CHECKSIG, OP_CLTV, OP_DROP},
+    script::Builder,
+    secp256k1::{Secp256k1, Verification, XOnlyPublicKey},
+    taproot::{TaprootBuilder, TaprootSpendInfo},
+};
+
+use crate::script_key::nums_key;
+
+/// Leaf spendable by `owner` once the chain reached `unlock_height`:
+/// `<height> OP_CHECKLOCKTIMEVERIFY OP_DROP <owner> OP_CHECKSIG`.
+pub fn vesting_script(owner: &XOnlyPublicKey, unlock_height: Height) -> ScriptBuf {
+    Builder::new()
+        .push_lock_time(LockTime::Blocks(unlock_height))
+        .push_opcode(OP_CLTV)
+        .push_opcode(OP_DROP)
+        .push_x_only_key(owner)
+        .push_opcode(OP_CHECKSIG)
+        .into_script()
+}
+
+/// Script key whose only spending path is [`vesting_script`]. The internal
+/// key is [`nums_key`] unless an `internal_key` able to spend at any time is
+/// given, e.g. a key of the issuer to claw back unvested allocations.
+pub fn vesting_script_key<C: Verification>(
+    secp: &Secp256k1<C>,
+    owner: &XOnlyPublicKey,
+    unlock_height: Height,
+    internal_key: Option<XOnlyPublicKey>,
+) -> TaprootSpendInfo {
+    TaprootBuilder::new()
+        .add_leaf(0, vesting_script(owner, unlock_height))
+        .expect("a root leaf fits an empty tree")
+        .finalize(secp, internal_key.unwrap_or_else(nums_key))
+        .expect("a single leaf is a complete tree")
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        absolute::Height,
+        secp256k1::{Secp256k1, SecretKey},
+        taproot::LeafVersion,
+    };
+
+    use super::{vesting_script, vesting_script_key};
+    use crate::script_key::{nums_key, nums_script_key};
+
+    #[test]
+    fn vesting_leaf() {
+        let secp = Secp256k1::new();
+        let owner = SecretKey::from_slice(&[1; 32]).unwrap().x_only_public_key(&secp).0;
+        let height = Height::from_consensus(900_000).unwrap();
+
+        let script = vesting_script(&owner, height);
+        assert_eq!(script.to_asm_string(), format!("OP_PUSHBYTES_3 a0bb0d OP_CLTV OP_DROP OP_PUSHBYTES_32 {} OP_CHECKSIG", owner));
+
+        let spend_info = vesting_script_key(&secp, &owner, height, None);
+        let root = spend_info.merkle_root().unwrap();
+        assert_eq!(spend_info.output_key(), nums_script_key(&secp, root));
+        assert_eq!(spend_info.internal_key(), nums_key());
+
+        let control_block = spend_info.control_block(&(script.clone(), LeafVersion::TapScript)).unwrap();
+        assert!(control_block.verify_taproot_commitment(&secp, spend_info.output_key().to_x_only_public_key(), &script));
+    }
+}
@@ -0,0 +1,257 @@
+//! Partially signed virtual transactions, the artifact passed between the
+//! parties of an asset transfer, following the BIP-174 roles: the creator
+//! sets the virtual inputs and outputs, signers add their signatures and
+//! derivation hints, combiners merge the copies they get back and the
+//! finalizer turns signatures into witnesses.
+
+use anyhow::bail;
+use bitcoin::{
+    OutPoint,
+    bip32::{DerivationPath, Fingerprint},
+    secp256k1::{XOnlyPublicKey, schnorr::Signature},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{amount::Amount, id::AssetId, witness::Witness};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VInput {
+    /// Anchor output the spent asset is committed in.
+    pub prev_out: OutPoint,
+    pub asset_id: AssetId,
+    pub script_key: XOnlyPublicKey,
+    pub amount: Amount,
+
+    /// Key the signer should derive to sign for the script key.
+    #[serde(default)]
+    pub derivation: Option<(Fingerprint, DerivationPath)>,
+
+    /// Key path signature of the script key.
+    #[serde(default)]
+    pub signature: Option<Signature>,
+
+    #[serde(default)]
+    pub final_witness: Option<Witness>,
+}
+
+impl VInput {
+    pub fn new(prev_out: OutPoint, asset_id: AssetId, script_key: XOnlyPublicKey, amount: Amount) -> Self {
+        VInput { prev_out, asset_id, script_key, amount, derivation: None, signature: None, final_witness: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VOutput {
+    pub amount: Amount,
+    pub script_key: XOnlyPublicKey,
+
+    /// Index of the anchor transaction output carrying this output.
+    pub anchor_output_index: u32,
+
+    /// Lets the receiver recognise its own output.
+    #[serde(default)]
+    pub derivation: Option<(Fingerprint, DerivationPath)>,
+}
+
+impl VOutput {
+    pub fn new(amount: Amount, script_key: XOnlyPublicKey, anchor_output_index: u32) -> Self {
+        VOutput { amount, script_key, anchor_output_index, derivation: None }
+    }
+}
+
+/// Only built through [`VPsbt::new`], deserializing included, so it always
+/// spends a single asset and balances.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawVPsbt")]
+pub struct VPsbt {
+    inputs: Vec<VInput>,
+    outputs: Vec<VOutput>,
+}
+
+/// Unchecked form a [`VPsbt`] is deserialized from.
+#[derive(Deserialize)]
+struct RawVPsbt {
+    inputs: Vec<VInput>,
+    outputs: Vec<VOutput>,
+}
+
+impl TryFrom<RawVPsbt> for VPsbt {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawVPsbt) -> anyhow::Result<Self> {
+        VPsbt::new(raw.inputs, raw.outputs)
+    }
+}
+
+/// Merges an optional field, both sides having set it to different values
+/// being a conflict.
+fn merge<T: PartialEq>(ours: &mut Option<T>, theirs: Option<T>, field: &str, idx: usize) -> anyhow::Result<()> {
+    match (ours.as_ref(), theirs) {
+        (Some(ours), Some(theirs)) if *ours != theirs => bail!("Conflicting {} for index {}", field, idx),
+        (None, theirs) => *ours = theirs,
+        _ => {},
+    }
+
+    Ok(())
+}
+
+impl VPsbt {
+    /// Creator role: the inputs must all spend the same asset, and the
+    /// outputs send exactly the amount spent.
+    pub fn new(inputs: Vec<VInput>, outputs: Vec<VOutput>) -> anyhow::Result<Self> {
+        let Some(first) = inputs.first() else {
+            bail!("Virtual transaction without inputs");
+        };
+        if inputs.iter().any(|input| input.asset_id != first.asset_id) {
+            bail!("Virtual transaction spending several assets");
+        }
+
+        let spent = Amount::checked_sum(inputs.iter().map(|input| input.amount));
+        let sent = Amount::checked_sum(outputs.iter().map(|output| output.amount));
+        if spent.is_none() || spent != sent {
+            bail!("Virtual transaction spends {:?} but sends {:?}", spent, sent);
+        }
+
+        Ok(VPsbt { inputs, outputs })
+    }
+
+    pub fn asset_id(&self) -> AssetId {
+        self.inputs[0].asset_id
+    }
+
+    pub fn inputs(&self) -> &[VInput] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[VOutput] {
+        &self.outputs
+    }
+
+    /// Signer role: sets the key path signature of input `idx`.
+    pub fn set_signature(&mut self, idx: usize, signature: Signature) -> anyhow::Result<()> {
+        let Some(input) = self.inputs.get_mut(idx) else {
+            bail!("No input {}", idx);
+        };
+
+        input.signature = Some(signature);
+        Ok(())
+    }
+
+    /// Combiner role: merges the signatures, witnesses and hints of `other`,
+    /// which must be a copy of the same virtual transaction.
+    pub fn combine(&mut self, other: VPsbt) -> anyhow::Result<()> {
+        let same_inputs = self.inputs.len() == other.inputs.len()
+            && self.inputs.iter().zip(&other.inputs).all(|(a, b)| {
+                (a.prev_out, a.asset_id, a.script_key, a.amount) == (b.prev_out, b.asset_id, b.script_key, b.amount)
+            });
+        let same_outputs = self.outputs.len() == other.outputs.len()
+            && self.outputs.iter().zip(&other.outputs).all(|(a, b)| {
+                (a.amount, a.script_key, a.anchor_output_index) == (b.amount, b.script_key, b.anchor_output_index)
+            });
+        if !same_inputs || !same_outputs {
+            bail!("Cannot combine different virtual transactions");
+        }
+
+        for (idx, (ours, theirs)) in self.inputs.iter_mut().zip(other.inputs).enumerate() {
+            merge(&mut ours.derivation, theirs.derivation, "derivation", idx)?;
+            merge(&mut ours.signature, theirs.signature, "signature", idx)?;
+            merge(&mut ours.final_witness, theirs.final_witness, "witness", idx)?;
+        }
+
+        for (idx, (ours, theirs)) in self.outputs.iter_mut().zip(other.outputs).enumerate() {
+            merge(&mut ours.derivation, theirs.derivation, "derivation", idx)?;
+        }
+
+        Ok(())
+    }
+
+    /// Finalizer role: turns the key path signature of every input into its
+    /// witness, dropping the fields only needed for signing.
+    pub fn finalize(&mut self) -> anyhow::Result<()> {
+        if let Some(idx) = self.inputs.iter().position(|input| input.final_witness.is_none() && input.signature.is_none()) {
+            bail!("Input {} isn't signed", idx);
+        }
+
+        for input in &mut self.inputs {
+            if let Some(signature) = input.signature.take() {
+                input.final_witness = Some(Witness::from(vec![signature.as_ref().to_vec()]));
+            }
+            input.derivation = None;
+        }
+
+        Ok(())
+    }
+
+    pub fn is_finalized(&self) -> bool {
+        self.inputs.iter().all(|input| input.final_witness.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        OutPoint, Txid,
+        bip32::{DerivationPath, Fingerprint},
+        hashes::Hash,
+        secp256k1::{Secp256k1, SecretKey, schnorr::Signature},
+    };
+
+    use super::{VInput, VOutput, VPsbt};
+    use crate::{amount::Amount, id::AssetId};
+
+    #[test]
+    fn combine_and_finalize() {
+        let secp = Secp256k1::new();
+        let key = |b| SecretKey::from_slice(&[b; 32]).unwrap().x_only_public_key(&secp).0;
+        let outpoint = |b| OutPoint::new(Txid::from_byte_array([b; 32]), 0);
+        let asset_id = AssetId::new([1; 32]);
+
+        let inputs = vec![VInput::new(outpoint(1), asset_id, key(1), Amount::from_units(60)), VInput::new(outpoint(2), asset_id, key(2), Amount::from_units(40))];
+        let outputs = vec![VOutput::new(Amount::from_units(70), key(3), 0), VOutput::new(Amount::from_units(30), key(4), 1)];
+        assert!(VPsbt::new(inputs.clone(), outputs[..1].to_vec()).is_err());
+        assert!(VPsbt::new(vec![inputs[0].clone(), VInput::new(outpoint(3), AssetId::new([2; 32]), key(2), Amount::from_units(40))], outputs.clone()).is_err());
+
+        let created = VPsbt::new(inputs, outputs).unwrap();
+        let (mut first, mut second) = (created.clone(), created.clone());
+
+        let sig = |b| Signature::from_slice(&[b; 64]).unwrap();
+        first.inputs[0].signature = Some(sig(1));
+        first.inputs[0].derivation = Some((Fingerprint::from([1; 4]), DerivationPath::from_str("m/86'/0'/0'").unwrap()));
+        second.inputs[1].signature = Some(sig(2));
+
+        let mut combined = created.clone();
+        assert!(combined.finalize().is_err());
+        combined.combine(first.clone()).unwrap();
+        combined.combine(second).unwrap();
+
+        // Conflicting signatures and different transactions.
+        let mut conflicting = first.clone();
+        conflicting.inputs[0].signature = Some(sig(3));
+        assert!(combined.clone().combine(conflicting).is_err());
+        let mut other = created.clone();
+        other.outputs[1].anchor_output_index = 2;
+        assert!(combined.clone().combine(other).is_err());
+
+        let json = serde_json::to_string(&combined).unwrap();
+        assert_eq!(serde_json::from_str::<VPsbt>(&json).unwrap(), combined);
+        assert!(combined.clone().set_signature(2, sig(1)).is_err());
+
+        combined.finalize().unwrap();
+        assert!(combined.is_finalized());
+        assert_eq!(combined.inputs[1].final_witness.as_ref().unwrap().iter().next().unwrap(), &[2; 64]);
+        assert!(combined.inputs[0].derivation.is_none() && combined.inputs[0].signature.is_none());
+    }
+
+    #[test]
+    fn deserialize_checks_invariants() {
+        assert!(serde_json::from_str::<VPsbt>(r#"{"inputs":[],"outputs":[]}"#).is_err());
+
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&[1; 32]).unwrap().x_only_public_key(&secp).0;
+        let input = VInput::new(OutPoint::new(Txid::from_byte_array([1; 32]), 0), AssetId::new([1; 32]), key, Amount::from_units(10));
+        let unbalanced = VPsbt { inputs: vec![input], outputs: vec![VOutput::new(Amount::from_units(11), key, 0)] };
+        assert!(serde_json::from_str::<VPsbt>(&serde_json::to_string(&unbalanced).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Wallet record of the provenance of script keys, so balances only count
+//! the assets held by keys we can spend.
+//!
+//! The [`ScriptKeyType`] of each declared key is stored as one byte under
+//! `k || x-only key`. Keys never declared are [`ScriptKeyType::Unknown`],
+//! except for the NUMS key which is always [`ScriptKeyType::Nums`].
+
+use anyhow::{bail, Context};
+use bitcoin::XOnlyPublicKey;
+use mssmt::store::KvStore;
+
+use crate::{
+    amount::Amount,
+    script_key::{ScriptKeyType, nums_key},
+};
+
+const SCRIPT_KEY_PREFIX: u8 = b'k';
+
+fn script_key_key(key: &XOnlyPublicKey) -> [u8; 33] {
+    let mut db_key = [SCRIPT_KEY_PREFIX; 33];
+    db_key[1..].copy_from_slice(&key.serialize());
+    db_key
+}
+
+pub struct ScriptKeyStore<S: KvStore> {
+    kv: S,
+}
+
+impl<S: KvStore> ScriptKeyStore<S> {
+    pub fn new(kv: S) -> Self {
+        ScriptKeyStore { kv }
+    }
+
+    /// Records the provenance of `key`, replacing the one declared before.
+    pub fn declare(&mut self, key: &XOnlyPublicKey, key_type: ScriptKeyType) -> anyhow::Result<()> {
+        if *key == nums_key() && key_type != ScriptKeyType::Nums {
+            bail!("The NUMS key can't be declared as {:?}", key_type);
+        }
+
+        self.kv.put(&script_key_key(key), &[key_type.to_u8()])
+    }
+
+    pub fn key_type(&self, key: &XOnlyPublicKey) -> anyhow::Result<ScriptKeyType> {
+        if *key == nums_key() {
+            return Ok(ScriptKeyType::Nums);
+        }
+
+        match self.kv.get(&script_key_key(key))? {
+            Some(stored) => decode_type(&stored),
+            None => Ok(ScriptKeyType::Unknown),
+        }
+    }
+
+    /// Declared keys of the given type.
+    pub fn keys(&self, key_type: ScriptKeyType) -> anyhow::Result<Vec<XOnlyPublicKey>> {
+        let mut keys = Vec::new();
+
+        for (db_key, stored) in self.kv.scan_prefix(&[SCRIPT_KEY_PREFIX])? {
+            if decode_type(&stored)? == key_type {
+                keys.push(XOnlyPublicKey::from_slice(db_key.get(1..).context("Invalid script key record")?).map_err(anyhow::Error::msg)?);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Sums the amounts held by spendable script keys, the others being
+    /// foreign, burnt or tombstones.
+    pub fn balance<I: IntoIterator<Item = (XOnlyPublicKey, Amount)>>(&self, holdings: I) -> anyhow::Result<Amount> {
+        let mut balance = Amount::ZERO;
+
+        for (key, amount) in holdings {
+            if self.key_type(&key)?.is_spendable() {
+                balance = balance.checked_add(amount).context("Balance overflows")?;
+            }
+        }
+
+        Ok(balance)
+    }
+
+    pub fn into_inner(self) -> S {
+        self.kv
+    }
+}
+
+fn decode_type(stored: &[u8]) -> anyhow::Result<ScriptKeyType> {
+    match stored {
+        &[b] => ScriptKeyType::from_u8(b).with_context(|| format!("Unknown script key type {}", b)),
+        _ => bail!("Invalid script key type record"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{TapNodeHash, hashes::Hash, secp256k1::Secp256k1};
+    use mssmt::store::MemoryKv;
+
+    use super::ScriptKeyStore;
+    use crate::{
+        amount::Amount,
+        script_key::{ScriptKeyType, nums_key, nums_script_key},
+    };
+
+    #[test]
+    fn balance_only_counts_local_keys() {
+        let secp = Secp256k1::verification_only();
+        let key = |b| nums_script_key(&secp, TapNodeHash::from_byte_array([b; 32])).to_x_only_public_key();
+
+        let mut store = ScriptKeyStore::new(MemoryKv::default());
+        store.declare(&key(1), ScriptKeyType::Local).unwrap();
+        store.declare(&key(2), ScriptKeyType::Local).unwrap();
+        store.declare(&key(3), ScriptKeyType::Declared).unwrap();
+        store.declare(&key(4), ScriptKeyType::Burn).unwrap();
+        assert!(store.declare(&nums_key(), ScriptKeyType::Local).is_err());
+
+        assert_eq!(store.key_type(&key(3)).unwrap(), ScriptKeyType::Declared);
+        assert_eq!(store.key_type(&key(5)).unwrap(), ScriptKeyType::Unknown);
+        assert_eq!(store.key_type(&nums_key()).unwrap(), ScriptKeyType::Nums);
+        assert_eq!(store.keys(ScriptKeyType::Local).unwrap().len(), 2);
+        assert_eq!(store.keys(ScriptKeyType::Burn).unwrap(), vec![key(4)]);
+
+        let holdings = [(key(1), 10), (key(2), 20), (key(3), 40), (key(4), 80), (key(5), 160), (nums_key(), 0)];
+        let holdings = holdings.map(|(key, units)| (key, Amount::from_units(units)));
+        assert_eq!(store.balance(holdings).unwrap(), Amount::from_units(30));
+        assert!(store.balance([(key(1), Amount::MAX), (key(2), Amount::from_units(1))]).is_err());
+
+        // Keys can be reclassified, e.g. once a declared key turns out to be
+        // ours, and the store reopens with what was declared.
+        let mut store = ScriptKeyStore::new(store.into_inner());
+        store.declare(&key(3), ScriptKeyType::Local).unwrap();
+        assert_eq!(store.balance(holdings).unwrap(), Amount::from_units(70));
+    }
+}
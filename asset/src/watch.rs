@@ -0,0 +1,146 @@
+//! Detection of incoming anchor outputs from BIP-158 compact block filters,
+//! so light clients notice receipts without a full node.
+//!
+//! The filters served by a neutrino style backend tell which blocks may pay
+//! a watched anchor output, false positives included. Only those blocks are
+//! then fetched and scanned. Outputs are watched as [`WatchedOutput`]s,
+//! the same [`InputAnchor`]s the PSBT inputs spending them later carry.
+
+use std::collections::HashMap;
+
+use bitcoin::{
+    Block, BlockHash, OutPoint, ScriptBuf, TxOut,
+    bip158::BlockFilter,
+    secp256k1::{Secp256k1, Verification},
+};
+
+use crate::anchor::InputAnchor;
+
+/// Anchor output expected to be received, committing to an asset tree under
+/// `internal_key`.
+pub type WatchedOutput = InputAnchor;
+
+/// Watched anchor output found in a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchorReceipt {
+    pub outpoint: OutPoint,
+    pub output: TxOut,
+    pub watched: WatchedOutput,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AnchorWatch {
+    scripts: HashMap<ScriptBuf, WatchedOutput>,
+}
+
+impl AnchorWatch {
+    /// Watches for outputs paying to `watched`.
+    pub fn watch<C: Verification>(&mut self, secp: &Secp256k1<C>, watched: WatchedOutput) {
+        self.scripts.insert(watched.script_pubkey(secp), watched);
+    }
+
+    pub fn unwatch<C: Verification>(&mut self, secp: &Secp256k1<C>, watched: &WatchedOutput) {
+        self.scripts.remove(&watched.script_pubkey(secp));
+    }
+
+    pub fn len(&self) -> usize {
+        self.scripts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    /// Whether the block of `filter` may pay a watched output, in which case
+    /// it should be fetched and passed to [`AnchorWatch::scan_block`].
+    pub fn matches(&self, filter: &BlockFilter, block_hash: &BlockHash) -> anyhow::Result<bool> {
+        if self.scripts.is_empty() {
+            return Ok(false);
+        }
+
+        filter.match_any(block_hash, self.scripts.keys().map(|script| script.as_bytes())).map_err(anyhow::Error::msg)
+    }
+
+    pub fn scan_block(&self, block: &Block) -> Vec<AnchorReceipt> {
+        let mut receipts = Vec::new();
+
+        for tx in &block.txdata {
+            let txid = tx.compute_txid();
+            for (vout, output) in tx.output.iter().enumerate() {
+                if let Some(watched) = self.scripts.get(&output.script_pubkey) {
+                    receipts.push(AnchorReceipt { outpoint: OutPoint::new(txid, vout as u32), output: output.clone(), watched: *watched });
+                }
+            }
+        }
+
+        receipts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        Amount, Block, BlockHash, CompactTarget, OutPoint, ScriptBuf, Sequence, TapNodeHash, Transaction, TxIn, TxMerkleNode, TxOut,
+        absolute::LockTime,
+        bip158::{BlockFilter, Error},
+        block::{Header, Version},
+        hashes::Hash,
+        secp256k1::Secp256k1,
+    };
+
+    use super::{AnchorWatch, WatchedOutput};
+    use crate::script_key::nums_key;
+
+    fn block(outputs: Vec<TxOut>) -> Block {
+        let coinbase = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint::null(), script_sig: ScriptBuf::new(), sequence: Sequence::MAX, witness: bitcoin::Witness::new() }],
+            output: outputs,
+        };
+        let header = Header {
+            version: Version::ONE,
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 0,
+            bits: CompactTarget::from_consensus(0),
+            nonce: 0,
+        };
+
+        Block { header, txdata: vec![coinbase] }
+    }
+
+    #[test]
+    fn detect_receipts() {
+        let secp = Secp256k1::verification_only();
+        let output = |b| WatchedOutput { internal_key: nums_key(), commitment_root: TapNodeHash::from_byte_array([b; 32]), sibling: None };
+
+        let mut watch = AnchorWatch::default();
+        let paying = block(vec![
+            TxOut { value: Amount::from_sat(1_000), script_pubkey: ScriptBuf::new_op_return([1]) },
+            TxOut { value: Amount::from_sat(1_000), script_pubkey: output(1).script_pubkey(&secp) },
+        ]);
+        let filter = BlockFilter::new_script_filter(&paying, |_| Err::<ScriptBuf, _>(Error::UtxoMissing(OutPoint::null()))).unwrap();
+        let block_hash = paying.block_hash();
+
+        assert!(!watch.matches(&filter, &block_hash).unwrap());
+
+        watch.watch(&secp, output(1));
+        watch.watch(&secp, output(2));
+        assert_eq!(watch.len(), 2);
+        assert!(watch.matches(&filter, &block_hash).unwrap());
+
+        let receipts = watch.scan_block(&paying);
+        assert_eq!(receipts.len(), 1);
+        assert_eq!(receipts[0].outpoint, OutPoint::new(paying.txdata[0].compute_txid(), 1));
+        assert_eq!(receipts[0].watched, output(1));
+
+        // The sibling branch is part of the watched script.
+        let watched = WatchedOutput { sibling: Some(TapNodeHash::from_byte_array([3; 32])), ..output(1) };
+        assert_ne!(watched.script_pubkey(&secp), output(1).script_pubkey(&secp));
+
+        watch.unwatch(&secp, &output(1));
+        assert!(!watch.matches(&filter, &block_hash).unwrap());
+        assert!(watch.scan_block(&paying).is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+//! Witness stacks of asset inputs and their size accounting.
+//!
+//! Witnesses are serialized like Bitcoin's: a CompactSize item count, then
+//! every item prefixed by its CompactSize length. Witness bytes weigh one
+//! weight unit each, so the serialized size is also the weight the witness
+//! adds to the transaction spending it.
+
+use anyhow::{bail, Context};
+use bitcoin::{
+    Weight,
+    hex::{DisplayHex, FromHex},
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tlv::varint::{encoded_len, read_compact_size, write_compact_size};
+
+/// Size of a BIP-340 signature with the default sighash type.
+pub const SCHNORR_SIG_LEN: usize = 64;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Witness(Vec<Vec<u8>>);
+
+impl Witness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Placeholder witness of a taproot key spend with the default sighash,
+    /// to estimate fees before signing.
+    pub fn key_spend_estimate() -> Self {
+        Witness(vec![vec![0; SCHNORR_SIG_LEN]])
+    }
+
+    /// Placeholder witness of a taproot script spend, given the stack items
+    /// the script consumes, the script itself and its control block.
+    pub fn script_spend_estimate(items: &[usize], script_len: usize, control_block_len: usize) -> Self {
+        let mut stack: Vec<Vec<u8>> = items.iter().map(|len| vec![0; *len]).collect();
+        stack.push(vec![0; script_len]);
+        stack.push(vec![0; control_block_len]);
+
+        Witness(stack)
+    }
+
+    pub fn push<T: Into<Vec<u8>>>(&mut self, item: T) {
+        self.0.push(item.into());
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> {
+        self.0.iter().map(Vec::as_slice)
+    }
+
+    /// Size of the serialized witness.
+    pub fn size(&self) -> usize {
+        encoded_len(self.0.len() as u64) + self.0.iter().map(|item| encoded_len(item.len() as u64) + item.len()).sum::<usize>()
+    }
+
+    pub fn weight(&self) -> Weight {
+        Weight::from_wu(self.size() as u64)
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.size());
+        write_compact_size(self.0.len() as u64, &mut out);
+        for item in &self.0 {
+            write_compact_size(item.len() as u64, &mut out);
+            out.extend_from_slice(item);
+        }
+
+        out
+    }
+
+    pub fn deserialize(mut bytes: &[u8]) -> anyhow::Result<Self> {
+        let count = read_compact_size(&mut bytes).context("Invalid witness item count")?;
+
+        // Every item takes at least a byte, this bounds the allocation.
+        if count > bytes.len() as u64 {
+            bail!("Witness of {} items in {} bytes", count, bytes.len());
+        }
+
+        let mut stack = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let len = read_compact_size(&mut bytes).context("Invalid witness item length")?;
+            if len > bytes.len() as u64 {
+                bail!("Witness item of {} bytes with {} bytes left", len, bytes.len());
+            }
+
+            let (item, rest) = bytes.split_at(len as usize);
+            stack.push(item.to_vec());
+            bytes = rest;
+        }
+
+        if !bytes.is_empty() {
+            bail!("{} trailing bytes after the witness", bytes.len());
+        }
+
+        Ok(Witness(stack))
+    }
+}
+
+/// Serialized as a list of hex strings, one per stack item.
+impl Serialize for Witness {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|item| item.to_lower_hex_string()))
+    }
+}
+
+impl<'de> Deserialize<'de> for Witness {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let items = Vec::<String>::deserialize(deserializer)?;
+        let stack = items.iter().map(|item| Vec::from_hex(item)).collect::<Result<_, _>>().map_err(serde::de::Error::custom)?;
+
+        Ok(Witness(stack))
+    }
+}
+
+impl From<Vec<Vec<u8>>> for Witness {
+    fn from(stack: Vec<Vec<u8>>) -> Self {
+        Witness(stack)
+    }
+}
+
+impl From<&bitcoin::Witness> for Witness {
+    fn from(witness: &bitcoin::Witness) -> Self {
+        Witness(witness.iter().map(<[u8]>::to_vec).collect())
+    }
+}
+
+impl From<&Witness> for bitcoin::Witness {
+    fn from(witness: &Witness) -> Self {
+        bitcoin::Witness::from_slice(&witness.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::consensus::encode::serialize;
+
+    use super::Witness;
+
+    #[test]
+    fn serialization_and_size() {
+        let mut witness = Witness::new();
+        witness.push(vec![1; 64]);
+        witness.push(vec![]);
+        witness.push(vec![2; 300]);
+
+        let bytes = witness.serialize();
+        assert_eq!(bytes.len(), witness.size());
+        assert_eq!(bytes, serialize(&bitcoin::Witness::from(&witness)));
+        assert_eq!(Witness::deserialize(&bytes).unwrap(), witness);
+        assert_eq!(Witness::from(&bitcoin::Witness::from(&witness)), witness);
+
+        assert!(Witness::deserialize(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Witness::deserialize(&[bytes.as_slice(), &[0]].concat()).is_err());
+        assert!(Witness::deserialize(&[0xfe, 0xff, 0xff, 0xff, 0xff]).is_err());
+
+        assert_eq!(Witness::new().size(), 1);
+        assert_eq!(Witness::key_spend_estimate().weight().to_wu(), 66);
+        assert_eq!(Witness::script_spend_estimate(&[64], 34, 33).size(), 1 + 65 + 35 + 34);
+
+        let json = serde_json::to_string(&Witness::from(vec![vec![0xab], vec![]])).unwrap();
+        assert_eq!(json, r#"["ab",""]"#);
+        assert_eq!(serde_json::from_str::<Witness>(&json).unwrap(), Witness::from(vec![vec![0xab], vec![]]));
+    }
+}
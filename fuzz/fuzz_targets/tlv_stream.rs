@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tlv::{Reader, Writer};
+
+// Streams accepted by the reader are canonical, so writing their records back
+// must give the exact same bytes.
+fuzz_target!(|data: &[u8]| {
+    let Ok(records) = Reader::new(data).records() else {
+        return;
+    };
+
+    let mut writer = Writer::new();
+    for record in records {
+        writer.record(record.tlv_type, record.value).unwrap();
+    }
+
+    assert_eq!(writer.finish(), data);
+});
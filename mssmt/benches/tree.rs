@@ -0,0 +1,77 @@
+//! Compare hashing backends with:
+//!   cargo bench -p mssmt --features testing
+//!   cargo bench -p mssmt --features testing,sha2
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use mssmt::{
+    testing::corpus::{Corpus, KeyDistribution},
+    tree::{NodeHash, Tree},
+};
+
+const LEAVES: usize = 1_000;
+
+fn random_leaves(n: usize) -> Vec<(NodeHash, [u8; 32], u64)> {
+    Corpus::new(0).leaves(KeyDistribution::Uniform, n, 1_000_000)
+}
+
+fn insert_and_hash(c: &mut Criterion) {
+    let leaves = random_leaves(LEAVES);
+
+    c.bench_function("insert 1000 leaves and hash root", |b| {
+        b.iter_batched(
+            Tree::init,
+            |mut tree| {
+                for (key, value, sum) in &leaves {
+                    tree.insert(key, *value, *sum).unwrap();
+                }
+                tree.root_hash()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn insert_only(c: &mut Criterion) {
+    let leaves = random_leaves(LEAVES);
+
+    c.bench_function("insert 1000 leaves", |b| {
+        b.iter_batched(
+            Tree::init,
+            |mut tree| {
+                for (key, value, sum) in &leaves {
+                    tree.insert(key, *value, *sum).unwrap();
+                }
+                tree
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn insert_near_collisions(c: &mut Criterion) {
+    let leaves = Corpus::new(0).leaves(KeyDistribution::NearCollisions { shared_bits: 240 }, LEAVES, 1_000_000);
+
+    c.bench_function("insert 1000 near colliding leaves and hash root", |b| {
+        b.iter_batched(
+            Tree::init,
+            |mut tree| {
+                for (key, value, sum) in &leaves {
+                    tree.insert(key, *value, *sum).unwrap();
+                }
+                tree.root_hash()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bulk_build(c: &mut Criterion) {
+    let leaves = random_leaves(LEAVES);
+
+    c.bench_function("build 1000 leaves bottom-up and hash root", |b| {
+        b.iter_batched(|| leaves.clone(), |leaves| Tree::from_leaves(leaves).unwrap().root_hash(), BatchSize::LargeInput)
+    });
+}
+
+criterion_group!(benches, insert_and_hash, insert_only, insert_near_collisions, bulk_build);
+criterion_main!(benches);
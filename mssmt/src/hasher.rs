@@ -0,0 +1,54 @@
+//! SHA-256 used to hash the tree nodes. `bitcoin_hashes` is used by default,
+//! the `sha2` feature switches to the `sha2` crate which picks SHA-NI/SIMD
+//! implementations at runtime when the CPU supports them.
+
+#[cfg(not(feature = "sha2"))]
+use bitcoin::hashes::{Hash, HashEngine, sha256};
+
+#[cfg(feature = "sha2")]
+use sha2::Digest;
+
+#[derive(Default)]
+pub(crate) struct Sha256 {
+    #[cfg(not(feature = "sha2"))]
+    engine: sha256::HashEngine,
+
+    #[cfg(feature = "sha2")]
+    engine: sha2::Sha256,
+}
+
+impl Sha256 {
+    pub(crate) fn input(&mut self, data: &[u8]) {
+        #[cfg(not(feature = "sha2"))]
+        self.engine.input(data);
+
+        #[cfg(feature = "sha2")]
+        self.engine.update(data);
+    }
+
+    pub(crate) fn finalize(self) -> [u8; 32] {
+        #[cfg(not(feature = "sha2"))]
+        return sha256::Hash::from_engine(self.engine).to_byte_array();
+
+        #[cfg(feature = "sha2")]
+        return self.engine.finalize().into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sha256;
+
+    #[test]
+    fn known_digest() {
+        let mut hasher = Sha256::default();
+        hasher.input(b"a");
+        hasher.input(b"bc");
+
+        // sha256("abc")
+        assert_eq!(
+            hasher.finalize().map(|b| format!("{b:02x}")).concat(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
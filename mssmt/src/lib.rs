@@ -1 +1,6 @@
-pub mod tree;
\ No newline at end of file
+mod hasher;
+pub mod metrics;
+pub mod store;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod tree;
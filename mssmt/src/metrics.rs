@@ -0,0 +1,108 @@
+//! Tree operation metrics, recorded through the `metrics` facade when the
+//! `metrics` feature is enabled. Exporting them (e.g. to Prometheus with
+//! `metrics-exporter-prometheus`) is left to the embedding application.
+//!
+//! Inserts are only timed with the feature enabled, so trees built without
+//! it don't pay for reading the clock.
+
+#[cfg(feature = "metrics")]
+use std::time::Duration;
+
+pub const INSERTS: &str = "mssmt_inserts_total";
+pub const INSERT_DURATION: &str = "mssmt_insert_duration_seconds";
+pub const INTEGRITY_FAILURES: &str = "mssmt_integrity_failures_total";
+
+#[cfg(feature = "metrics")]
+pub(crate) fn record_insert(elapsed: Duration) {
+    metrics::counter!(INSERTS).increment(1);
+    metrics::histogram!(INSERT_DURATION).record(elapsed.as_secs_f64());
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn record_integrity_failures(count: usize) {
+    metrics::counter!(INTEGRITY_FAILURES).increment(count as u64);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn record_integrity_failures(_count: usize) {}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use metrics::{Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+
+    use crate::tree::{NodeHash, Tree};
+
+    use super::{INSERT_DURATION, INSERTS, INTEGRITY_FAILURES};
+
+    /// Recorder keeping every counter increment and histogram value by name.
+    #[derive(Default)]
+    struct TestRecorder {
+        values: Arc<Mutex<Vec<(String, f64)>>>,
+    }
+
+    struct Handle {
+        name: String,
+        values: Arc<Mutex<Vec<(String, f64)>>>,
+    }
+
+    impl CounterFn for Handle {
+        fn increment(&self, value: u64) {
+            self.values.lock().unwrap().push((self.name.clone(), value as f64));
+        }
+
+        fn absolute(&self, _value: u64) {}
+    }
+
+    impl HistogramFn for Handle {
+        fn record(&self, value: f64) {
+            self.values.lock().unwrap().push((self.name.clone(), value));
+        }
+    }
+
+    impl TestRecorder {
+        fn handle(&self, key: &Key) -> Arc<Handle> {
+            Arc::new(Handle { name: key.name().to_string(), values: self.values.clone() })
+        }
+
+        fn recorded(&self, name: &str) -> Vec<f64> {
+            self.values.lock().unwrap().iter().filter(|(n, _)| n == name).map(|(_, v)| *v).collect()
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+        fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+        fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.handle(key))
+        }
+
+        fn register_gauge(&self, _key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(self.handle(key))
+        }
+    }
+
+    #[test]
+    fn records_tree_operations() {
+        let recorder = TestRecorder::default();
+
+        metrics::with_local_recorder(&recorder, || {
+            let mut ms_tree = Tree::init();
+            ms_tree.insert(&NodeHash::new([1; 32]), [1; 32], 10).unwrap();
+            ms_tree.insert(&NodeHash::new([2; 32]), [2; 32], 20).unwrap();
+            assert!(ms_tree.verify_integrity().is_ok());
+        });
+
+        assert_eq!(recorder.recorded(INSERTS), vec![1.0, 1.0]);
+        assert_eq!(recorder.recorded(INSERT_DURATION).len(), 2);
+        assert!(recorder.recorded(INSERT_DURATION).iter().all(|secs| *secs >= 0.0));
+        assert_eq!(recorder.recorded(INTEGRITY_FAILURES), vec![0.0]);
+    }
+}
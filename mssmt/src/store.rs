@@ -0,0 +1,120 @@
+//! Ordered key/value stores the tree can be persisted into, see
+//! [`Tree::persist`](crate::tree::Tree::persist).
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "encryption")]
+mod encrypted;
+
+#[cfg(feature = "encryption")]
+pub use encrypted::{DEFAULT_ROUNDS, EncryptedKv};
+
+pub trait KvStore {
+    fn get(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>>;
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> anyhow::Result<()>;
+
+    fn delete(&mut self, key: &[u8]) -> anyhow::Result<()>;
+
+    /// Returns all the entries whose key starts with `prefix`, in key order.
+    fn scan_prefix(&self, prefix: &[u8]) -> anyhow::Result<Vec<(Vec<u8>, Vec<u8>)>>;
+}
+
+/// Store keeping everything in memory, mostly useful for tests.
+#[derive(Default)]
+pub struct MemoryKv {
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl MemoryKv {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl KvStore for MemoryKv {
+    fn get(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.entries.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &[u8]) -> anyhow::Result<()> {
+        self.entries.remove(key);
+        Ok(())
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> anyhow::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        Ok(self.entries
+            .range(prefix.to_vec()..)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+}
+
+#[cfg(feature = "sled")]
+impl KvStore for sled::Tree {
+    fn get(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(sled::Tree::get(self, key)?.map(|v| v.to_vec()))
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        self.insert(key, value)?;
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &[u8]) -> anyhow::Result<()> {
+        self.remove(key)?;
+        Ok(())
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> anyhow::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        sled::Tree::scan_prefix(self, prefix)
+            .map(|entry| {
+                let (k, v) = entry?;
+                Ok((k.to_vec(), v.to_vec()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KvStore, MemoryKv};
+
+    fn check_store<S: KvStore>(kv: &mut S) {
+        kv.put(b"ab", b"1").unwrap();
+        kv.put(b"b", b"2").unwrap();
+        kv.put(b"aa", b"3").unwrap();
+        kv.put(b"a", b"4").unwrap();
+
+        assert_eq!(kv.get(b"b").unwrap(), Some(b"2".to_vec()));
+
+        let keys: Vec<Vec<u8>> = kv.scan_prefix(b"a").unwrap().into_iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, vec![b"a".to_vec(), b"aa".to_vec(), b"ab".to_vec()]);
+
+        kv.delete(b"aa").unwrap();
+        assert_eq!(kv.get(b"aa").unwrap(), None);
+        assert_eq!(kv.scan_prefix(b"a").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn memory_kv() {
+        check_store(&mut MemoryKv::default());
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn sled_kv() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        check_store(&mut db.open_tree("mssmt").unwrap());
+    }
+}
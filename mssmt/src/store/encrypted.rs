@@ -0,0 +1,303 @@
+//! Store wrapper encrypting values with AES-256-GCM under a key derived from
+//! a passphrase with PBKDF2-HMAC-SHA256.
+//!
+//! Keys stay in clear so prefix scans keep working, values are stored as
+//! `nonce || ciphertext` with their key as associated data, so a value can't
+//! be moved under another key unnoticed. The salt and round count are stored
+//! in the wrapped store along with a check value telling a wrong passphrase
+//! apart from corrupted data.
+//!
+//! Rotating the passphrase first stages every value encrypted under the new
+//! key, then switches to the new parameters in a single write and finally
+//! moves the staged values over the old ones. A rotation interrupted before
+//! the switch leaves the store under the old passphrase, one interrupted
+//! after it under the new one, and unlocking completes or drops the staged
+//! values accordingly.
+
+use aes_gcm::{
+    Aes256Gcm, KeyInit, Nonce,
+    aead::{Aead, Payload},
+};
+use anyhow::{anyhow, bail, Context};
+use sha2::Sha256;
+
+use super::KvStore;
+
+/// PBKDF2 rounds for new stores, following the OWASP recommendation for
+/// HMAC-SHA256.
+pub const DEFAULT_ROUNDS: u32 = 600_000;
+
+const RESERVED_PREFIX: &[u8] = b"\xffencryption/";
+const PARAMS_KEY: &[u8] = b"\xffencryption/params";
+const STAGED_PREFIX: &[u8] = b"\xffencryption/staged/";
+const CHECK_VALUE: &[u8] = b"mssmt encrypted store";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// `salt || rounds || check`, the check value being encrypted with the
+/// params key as associated data.
+const CHECK_OFFSET: usize = SALT_LEN + 4;
+
+pub struct EncryptedKv<S: KvStore> {
+    inner: S,
+    cipher: Aes256Gcm,
+}
+
+fn derive_cipher(passphrase: &[u8], salt: &[u8], rounds: u32) -> Aes256Gcm {
+    let mut key = [0; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase, salt, rounds, &mut key);
+
+    Aes256Gcm::new(&key.into())
+}
+
+fn encrypt(cipher: &Aes256Gcm, key: &[u8], value: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let nonce: [u8; NONCE_LEN] = rand::random();
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg: value, aad: key }).map_err(|_| anyhow!("Encryption failed"))?;
+
+    Ok([&nonce[..], &ciphertext].concat())
+}
+
+fn decrypt(cipher: &Aes256Gcm, key: &[u8], stored: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if stored.len() < NONCE_LEN {
+        bail!("Encrypted value too short");
+    }
+
+    let (nonce, ciphertext) = stored.split_at(NONCE_LEN);
+    cipher.decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: key }).map_err(|_| anyhow!("Failed to decrypt the value of {:x?}", key))
+}
+
+fn staged_key(key: &[u8]) -> Vec<u8> {
+    [STAGED_PREFIX, key].concat()
+}
+
+/// Derives a cipher with a new salt, returning it with its params record.
+fn new_params(passphrase: &[u8], rounds: u32) -> anyhow::Result<(Aes256Gcm, Vec<u8>)> {
+    let salt: [u8; SALT_LEN] = rand::random();
+    let cipher = derive_cipher(passphrase, &salt, rounds);
+    let check = encrypt(&cipher, PARAMS_KEY, CHECK_VALUE)?;
+
+    Ok((cipher, [&salt[..], &rounds.to_be_bytes(), &check].concat()))
+}
+
+fn check_key(key: &[u8]) -> anyhow::Result<()> {
+    if key.starts_with(RESERVED_PREFIX) {
+        bail!("Key reserved by the encrypted store");
+    }
+
+    Ok(())
+}
+
+impl<S: KvStore> EncryptedKv<S> {
+    /// Unlocks `inner` with `passphrase`, setting it up with
+    /// [`DEFAULT_ROUNDS`] if it was never encrypted.
+    pub fn unlock(inner: S, passphrase: &[u8]) -> anyhow::Result<Self> {
+        Self::unlock_with_rounds(inner, passphrase, DEFAULT_ROUNDS)
+    }
+
+    /// Same as [`EncryptedKv::unlock`], `rounds` being only used to set up a
+    /// new store.
+    pub fn unlock_with_rounds(mut inner: S, passphrase: &[u8], rounds: u32) -> anyhow::Result<Self> {
+        let Some(params) = inner.get(PARAMS_KEY)? else {
+            let (cipher, params) = new_params(passphrase, rounds)?;
+            inner.put(PARAMS_KEY, &params)?;
+            return Ok(EncryptedKv { inner, cipher });
+        };
+
+        if params.len() <= CHECK_OFFSET {
+            bail!("Invalid encryption parameters");
+        }
+        let rounds = u32::from_be_bytes(params[SALT_LEN..CHECK_OFFSET].try_into()?);
+        let cipher = derive_cipher(passphrase, &params[..SALT_LEN], rounds);
+
+        if decrypt(&cipher, PARAMS_KEY, &params[CHECK_OFFSET..]).ok().as_deref() != Some(CHECK_VALUE) {
+            bail!("Wrong passphrase");
+        }
+
+        let mut kv = EncryptedKv { inner, cipher };
+        kv.complete_rotation().context("Failed to complete an interrupted rotation")?;
+
+        Ok(kv)
+    }
+
+    /// Moves the values staged by a rotation interrupted after its switch to
+    /// the current key, and drops those of one interrupted before it.
+    fn complete_rotation(&mut self) -> anyhow::Result<()> {
+        for (staged, stored) in self.inner.scan_prefix(STAGED_PREFIX)? {
+            let key = &staged[STAGED_PREFIX.len()..];
+            if decrypt(&self.cipher, key, &stored).is_ok() {
+                self.inner.put(key, &stored)?;
+            }
+            self.inner.delete(&staged)?;
+        }
+
+        Ok(())
+    }
+
+    /// Drops the key, giving back the wrapped store.
+    pub fn lock(self) -> S {
+        self.inner
+    }
+
+    /// Re-encrypts every value under a key derived from `passphrase` with a
+    /// new salt. If interrupted, the store unlocks with the old passphrase
+    /// until the new parameters are written, and with the new one after.
+    pub fn rotate(&mut self, passphrase: &[u8], rounds: u32) -> anyhow::Result<()> {
+        // Leftovers of an earlier failed rotation, staged under another key.
+        self.complete_rotation()?;
+
+        let (cipher, params) = new_params(passphrase, rounds)?;
+        let entries = self.scan_prefix(&[])?;
+        for (key, value) in &entries {
+            self.inner.put(&staged_key(key), &encrypt(&cipher, key, value)?)?;
+        }
+
+        self.inner.put(PARAMS_KEY, &params)?;
+        self.cipher = cipher;
+
+        self.complete_rotation()
+    }
+}
+
+impl<S: KvStore> KvStore for EncryptedKv<S> {
+    fn get(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+        check_key(key)?;
+        self.inner.get(key)?.map(|stored| decrypt(&self.cipher, key, &stored)).transpose()
+    }
+
+    fn put(&mut self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+        check_key(key)?;
+        self.inner.put(key, &encrypt(&self.cipher, key, value)?)
+    }
+
+    fn delete(&mut self, key: &[u8]) -> anyhow::Result<()> {
+        check_key(key)?;
+        self.inner.delete(key)
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> anyhow::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.inner
+            .scan_prefix(prefix)?
+            .into_iter()
+            .filter(|(key, _)| !key.starts_with(RESERVED_PREFIX))
+            .map(|(key, stored)| {
+                let value = decrypt(&self.cipher, &key, &stored)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        store::{KvStore, MemoryKv},
+        tree::{NodeHash, Tree},
+    };
+
+    use super::{EncryptedKv, STAGED_PREFIX};
+
+    // Keeps the tests fast, real stores use DEFAULT_ROUNDS.
+    const ROUNDS: u32 = 10;
+
+    #[test]
+    fn encrypt_lock_and_rotate() {
+        let mut kv = EncryptedKv::unlock_with_rounds(MemoryKv::default(), b"secret", ROUNDS).unwrap();
+        kv.put(b"a", b"1").unwrap();
+        kv.put(b"b", b"2").unwrap();
+        assert_eq!(kv.get(b"a").unwrap(), Some(b"1".to_vec()));
+        assert_eq!(kv.scan_prefix(&[]).unwrap().len(), 2);
+        assert!(kv.put(b"\xffencryption/params", b"").is_err());
+
+        let mut ms_tree = Tree::init();
+        ms_tree.insert(&NodeHash::new([1; 32]), [1; 32], 10).unwrap();
+        let root = ms_tree.persist(&mut kv).unwrap();
+
+        let mut inner = kv.lock();
+        assert_ne!(inner.get(b"a").unwrap(), Some(b"1".to_vec()));
+
+        // Values can't be moved under another key.
+        let moved = inner.get(b"a").unwrap().unwrap();
+        inner.put(b"c", &moved).unwrap();
+        let kv = EncryptedKv::unlock(inner, b"secret").unwrap();
+        assert!(kv.get(b"c").is_err());
+        assert!(EncryptedKv::unlock(kv.lock(), b"wrong").is_err());
+
+        let mut kv = EncryptedKv::unlock_with_rounds(MemoryKv::default(), b"secret", ROUNDS).unwrap();
+        let mut ms_tree = Tree::init();
+        ms_tree.insert(&NodeHash::new([1; 32]), [1; 32], 10).unwrap();
+        ms_tree.persist(&mut kv).unwrap();
+        kv.rotate(b"new secret", ROUNDS).unwrap();
+        let inner = kv.lock();
+        let kv = EncryptedKv::unlock(inner, b"new secret").unwrap();
+        assert_eq!(Tree::load(&kv).unwrap().root_hash(), root);
+    }
+
+    /// Store failing every write after the first `writes_left`, as if the
+    /// process was killed.
+    struct CrashingKv {
+        inner: MemoryKv,
+        writes_left: usize,
+    }
+
+    impl CrashingKv {
+        fn write(&mut self) -> anyhow::Result<()> {
+            if self.writes_left == 0 {
+                anyhow::bail!("Killed");
+            }
+            self.writes_left -= 1;
+            Ok(())
+        }
+    }
+
+    impl KvStore for CrashingKv {
+        fn get(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+            self.inner.get(key)
+        }
+
+        fn put(&mut self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+            self.write()?;
+            self.inner.put(key, value)
+        }
+
+        fn delete(&mut self, key: &[u8]) -> anyhow::Result<()> {
+            self.write()?;
+            self.inner.delete(key)
+        }
+
+        fn scan_prefix(&self, prefix: &[u8]) -> anyhow::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+            self.inner.scan_prefix(prefix)
+        }
+    }
+
+    #[test]
+    fn interrupted_rotation() {
+        let entries: Vec<_> = (0..4u8).map(|b| (vec![b], vec![b; 8])).collect();
+
+        for writes in 0.. {
+            let mut kv = EncryptedKv::unlock_with_rounds(MemoryKv::default(), b"old", ROUNDS).unwrap();
+            for (key, value) in &entries {
+                kv.put(key, value).unwrap();
+            }
+
+            let mut kv = EncryptedKv::unlock(CrashingKv { inner: kv.lock(), writes_left: writes }, b"old").unwrap();
+            let rotated = kv.rotate(b"new", ROUNDS).is_ok();
+            let inner = kv.lock().inner;
+
+            // Exactly one passphrase unlocks the store, with every value
+            // readable and nothing left staged.
+            let old = EncryptedKv::unlock(MemoryKv { entries: inner.entries.clone() }, b"old");
+            let new = EncryptedKv::unlock(inner, b"new");
+            assert!(old.is_ok() != new.is_ok(), "{} writes", writes);
+            assert!(!rotated || new.is_ok());
+
+            let kv = old.or(new).unwrap();
+            assert_eq!(kv.scan_prefix(&[]).unwrap(), entries, "{} writes", writes);
+            assert!(kv.lock().scan_prefix(STAGED_PREFIX).unwrap().is_empty());
+
+            if rotated {
+                break;
+            }
+        }
+    }
+}
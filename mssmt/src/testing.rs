@@ -0,0 +1,4 @@
+//! Helpers for tests and benchmarks of code built on the tree, behind the
+//! `testing` feature.
+
+pub mod corpus;
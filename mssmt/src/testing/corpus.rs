@@ -0,0 +1,114 @@
+//! Reproducible leaf sets stressing different tree shapes.
+//!
+//! Everything is drawn from a ChaCha8 stream seeded by the caller, so the
+//! same seed gives the same corpus on every platform and version of `rand`.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::tree::NodeHash;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyDistribution {
+    Uniform,
+
+    /// Keys sharing their first `prefix_bits` path bits with one of
+    /// `clusters` random prefixes, like the leaves of a few busy assets.
+    Clustered { clusters: usize, prefix_bits: usize },
+
+    /// Keys all sharing their first `shared_bits` path bits, forcing long
+    /// shared paths down to where they diverge. Keys repeat once there are
+    /// more of them than the remaining bits can tell apart.
+    NearCollisions { shared_bits: usize },
+}
+
+pub struct Corpus {
+    rng: ChaCha8Rng,
+}
+
+/// Copies the first `bits` path bits of `prefix` into `key`, bit `i` of the
+/// path being bit `i % 8` of byte `i / 8`.
+fn with_prefix(mut key: [u8; 32], prefix: &[u8; 32], bits: usize) -> [u8; 32] {
+    for idx in 0..bits.min(256) {
+        let mask = 1 << (idx % 8);
+        key[idx / 8] = (key[idx / 8] & !mask) | (prefix[idx / 8] & mask);
+    }
+    key
+}
+
+impl Corpus {
+    pub fn new(seed: u64) -> Self {
+        Corpus { rng: ChaCha8Rng::seed_from_u64(seed) }
+    }
+
+    pub fn keys(&mut self, distribution: KeyDistribution, n: usize) -> Vec<NodeHash> {
+        match distribution {
+            KeyDistribution::Uniform => (0..n).map(|_| NodeHash::new(self.rng.r#gen())).collect(),
+
+            KeyDistribution::Clustered { clusters, prefix_bits } => {
+                let prefixes: Vec<[u8; 32]> = (0..clusters.max(1)).map(|_| self.rng.r#gen()).collect();
+
+                (0..n)
+                    .map(|_| {
+                        let prefix = &prefixes[self.rng.gen_range(0..prefixes.len())];
+                        NodeHash::new(with_prefix(self.rng.r#gen(), prefix, prefix_bits))
+                    })
+                    .collect()
+            },
+
+            KeyDistribution::NearCollisions { shared_bits } => {
+                let prefix: [u8; 32] = self.rng.r#gen();
+                (0..n).map(|_| NodeHash::new(with_prefix(self.rng.r#gen(), &prefix, shared_bits))).collect()
+            },
+        }
+    }
+
+    /// Sums uniform in `0..=max`. A `max` of `u64::MAX / n` or more lets the
+    /// root sum of `n` leaves overflow.
+    pub fn sums(&mut self, n: usize, max: u64) -> Vec<u64> {
+        (0..n).map(|_| self.rng.gen_range(0..=max)).collect()
+    }
+
+    /// Leaves with keys following `distribution`, random values and sums up
+    /// to `max_sum`.
+    pub fn leaves(&mut self, distribution: KeyDistribution, n: usize, max_sum: u64) -> Vec<(NodeHash, [u8; 32], u64)> {
+        let keys = self.keys(distribution, n);
+        let sums = self.sums(n, max_sum);
+
+        keys.into_iter().zip(sums).map(|(key, sum)| (key, self.rng.r#gen(), sum)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Corpus, KeyDistribution};
+    use crate::tree::Tree;
+
+    #[test]
+    fn reproducible_shapes() {
+        let uniform = Corpus::new(7).leaves(KeyDistribution::Uniform, 100, 1_000);
+        assert_eq!(uniform, Corpus::new(7).leaves(KeyDistribution::Uniform, 100, 1_000));
+        assert_ne!(uniform, Corpus::new(8).leaves(KeyDistribution::Uniform, 100, 1_000));
+        assert!(uniform.iter().all(|(_, _, sum)| *sum <= 1_000));
+
+        let clustered = Corpus::new(7).keys(KeyDistribution::Clustered { clusters: 2, prefix_bits: 16 }, 100);
+        let mut prefixes: Vec<[u8; 2]> = clustered.iter().map(|key| [key.as_bytes()[0], key.as_bytes()[1]]).collect();
+        prefixes.sort();
+        prefixes.dedup();
+        assert!(prefixes.len() <= 2);
+
+        // Only the last 4 bits of the path are free.
+        let near = Corpus::new(7).keys(KeyDistribution::NearCollisions { shared_bits: 252 }, 100);
+        assert!(near.iter().all(|key| key.as_bytes()[..31] == near[0].as_bytes()[..31]));
+        assert!(near.iter().all(|key| key.as_bytes()[31] & 0x0f == near[0].as_bytes()[31] & 0x0f));
+
+        let mut ms_tree = Tree::init();
+        for (key, value, sum) in Corpus::new(7).leaves(KeyDistribution::NearCollisions { shared_bits: 250 }, 50, 10) {
+            ms_tree.insert(&key, value, sum).unwrap();
+        }
+        assert!(ms_tree.verify_integrity().is_ok());
+
+        let sums = Corpus::new(7).sums(8, u64::MAX / 2);
+        assert!(sums.iter().try_fold(0u64, |total, sum| total.checked_add(*sum)).is_none());
+    }
+}
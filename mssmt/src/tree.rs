@@ -1,24 +1,76 @@
-use std::{fmt::Display, str::FromStr};
-
-use bitcoin::{hashes::{Hash, HashEngine, sha256}, hex::{DisplayHex, FromHex}};
+use std::{collections::HashSet, fmt::Display, str::FromStr, sync::{OnceLock, mpsc::Sender}};
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+use anyhow::bail;
+use bitcoin::{
+    hashes::{Hash, sha256},
+    hex::{DisplayHex, FromHex},
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{hasher::Sha256, metrics};
+
+mod builder;
+mod bulk;
+mod diff;
+mod hybrid;
+mod persist;
+mod render;
+mod stats;
+mod subscribe;
+
+pub use builder::NodeBuilder;
+pub use diff::{LeafDiff, diff_trees};
+pub use hybrid::HybridTree;
+pub use persist::{ROOT_KEY, StoreReport};
+pub use render::RenderFormat;
+pub use stats::TreeStats;
+pub use subscribe::RootUpdate;
 
 pub const MAX_TREE_LEVEL: usize = 256;
 pub const LAST_BIT_INDEX: usize = MAX_TREE_LEVEL - 1;
 
 /// Represents the key of a MS-SMT
 /// A key in a MS-SMT 256 bit since our hash function used here is sha256
-#[derive(Clone)]
-pub struct NodeHash(pub [u8; 32]);
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+pub struct NodeHash([u8; 32]);
 
 impl NodeHash {
     pub fn new(b: [u8; 32]) -> Self {
         NodeHash(b)
     }
-}
 
-impl Default for NodeHash {
-    fn default() -> Self {
-        NodeHash([0; 32])
+    pub fn from_slice(b: &[u8]) -> Result<Self, String> {
+        let res = <[u8; 32]>::try_from(b).map_err(|_| format!("Invalid slice length {}", b.len()))?;
+
+        Ok(NodeHash(res))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn to_byte_array(self) -> [u8; 32] {
+        self.0
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.as_hex().to_string()
+    }
+
+    /// Returns the bit of the key at `idx`, bits are read starting from the
+    /// least significant one of each byte. A 0 bit means going left.
+    fn bit(&self, idx: usize) -> u8 {
+        (self.0[idx / 8] >> (idx % 8)) & 1
+    }
+
+    fn set_bit(&mut self, idx: usize) {
+        self.0[idx / 8] |= 1 << (idx % 8);
+    }
+
+    fn clear_bit(&mut self, idx: usize) {
+        self.0[idx / 8] &= !(1 << (idx % 8));
     }
 }
 
@@ -35,47 +87,159 @@ impl FromStr for NodeHash {
     }
 }
 
+impl From<[u8; 32]> for NodeHash {
+    fn from(b: [u8; 32]) -> Self {
+        NodeHash(b)
+    }
+}
+
+impl From<NodeHash> for [u8; 32] {
+    fn from(hash: NodeHash) -> Self {
+        hash.0
+    }
+}
+
+impl From<sha256::Hash> for NodeHash {
+    fn from(hash: sha256::Hash) -> Self {
+        NodeHash(hash.to_byte_array())
+    }
+}
+
+impl From<NodeHash> for sha256::Hash {
+    fn from(hash: NodeHash) -> Self {
+        sha256::Hash::from_byte_array(hash.0)
+    }
+}
+
+impl TryFrom<&[u8]> for NodeHash {
+    type Error = String;
+
+    fn try_from(b: &[u8]) -> Result<Self, Self::Error> {
+        Self::from_slice(b)
+    }
+}
+
 impl Display for NodeHash {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.0.as_hex().to_string())
+        f.write_str(&self.to_hex())
     }
 }
 
+/// Serialized as a hex string.
+impl Serialize for NodeHash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for NodeHash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// sha256(left || right || sum)
+fn branch_hash(left: &NodeHash, right: &NodeHash, sum: u64) -> NodeHash {
+    let mut hasher = Sha256::default();
+
+    hasher.input(&left.0);
+    hasher.input(&right.0);
+    hasher.input(&sum.to_be_bytes());
+
+    NodeHash(hasher.finalize())
+}
+
+/// sha256(value || sum)
+fn leaf_hash(value: &[u8; 32], sum: u64) -> NodeHash {
+    let mut hasher = Sha256::default();
+
+    hasher.input(value);
+    hasher.input(&sum.to_be_bytes());
+
+    NodeHash(hasher.finalize())
+}
+
+/// Branch built outside of a tree, e.g. from a merkle proof. It only keeps
+/// the hashes of its children, trees hold their nodes in their arena.
 #[derive(Clone)]
-struct BranchNode {
-    left: Box<Node>,
-    right: Box<Node>,
-    hash: Option<NodeHash>,
+pub struct BranchNode {
+    left: NodeHash,
+    right: NodeHash,
+    hash: OnceLock<NodeHash>,
     sum: u64,
 }
 
 impl BranchNode {
-    pub fn new(left: Node, right: Node) -> Self {
-        BranchNode { left: Box::new(left), right: Box::new(right), hash: None, sum: 0 }
+    fn new(left: Node, right: Node) -> Self {
+        let sum = left.sum() + right.sum();
+        BranchNode { left: left.hash(), right: right.hash(), hash: OnceLock::new(), sum }
+    }
+
+    /// Builds a branch from its children, failing if their sums overflow.
+    pub fn from_children(left: Node, right: Node) -> anyhow::Result<Self> {
+        if left.sum().checked_add(right.sum()).is_none() {
+            bail!("Branch sum overflows");
+        }
+
+        Ok(Self::new(left, right))
+    }
+
+    pub fn left_hash(&self) -> NodeHash {
+        self.left
+    }
+
+    pub fn right_hash(&self) -> NodeHash {
+        self.right
+    }
+
+    pub fn sum(&self) -> u64 {
+        self.sum
     }
 }
 
-#[derive(Clone)]
-struct LeafNode {
+#[derive(Clone, Default, Debug)]
+pub struct LeafNode {
     value: [u8; 32],
     sum: u64,
-    hash: Option<NodeHash>,
+    hash: OnceLock<NodeHash>,
 }
 
-impl Default for LeafNode {
-    fn default() -> Self {
-        LeafNode { value: [0; 32], sum: 0, hash: None }
+impl LeafNode {
+    pub fn new(value: [u8; 32], sum: u64) -> Self {
+        LeafNode { value, sum, hash: OnceLock::new() }
+    }
+
+    pub fn value(&self) -> &[u8; 32] {
+        &self.value
+    }
+
+    pub fn sum(&self) -> u64 {
+        self.sum
+    }
+
+    fn node_hash(&self) -> NodeHash {
+        *self.hash.get_or_init(|| leaf_hash(&self.value, self.sum))
     }
 }
 
-#[derive(Clone)]
-#[allow(dead_code)]
+// The cached hash is left out, it follows from the value and sum.
+impl PartialEq for LeafNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value && self.sum == other.sum
+    }
+}
 
-enum Node {
+impl Eq for LeafNode {}
+
+/// Single node built outside of a tree, no node owns its children so
+/// building a root with [`NodeBuilder`] only keeps one node per level.
+#[derive(Clone)]
+pub enum Node {
     Branch(BranchNode),
     Leaf(LeafNode),
 
-    ComputedNode {
+    Computed {
         hash: NodeHash,
         sum: u64
     },
@@ -85,48 +249,20 @@ enum Node {
 
 impl Node {
 
-    fn hash(&mut self) -> NodeHash {
+    /// Returns the node hash, computing and caching it the first time.
+    pub fn hash(&self) -> NodeHash {
 
         match self  {
             Self::Branch (bn) => {
-
-                if bn.hash.is_some() {
-                    return bn.hash.as_ref().unwrap().clone();
-                }
-
-                let left_hash = bn.left.hash();
-                let right_hash = bn.right.hash();
-
-                let mut hash_engine = sha256::HashEngine::default();
-
-                hash_engine.input(&left_hash.0);
-                hash_engine.input(&right_hash.0);
-                hash_engine.input(&bn.sum.to_be_bytes());
-
-                let res = sha256::Hash::from_engine(hash_engine);
-                bn.hash = Some(NodeHash(res.to_byte_array()));
-
-                bn.hash.as_ref().unwrap().clone()
+                *bn.hash.get_or_init(|| branch_hash(&bn.left, &bn.right, bn.sum))
             },
 
             Self::Leaf (ln) => {
-
-                if ln.hash.is_some() {
-                    return ln.hash.as_ref().unwrap().clone();
-                }
-
-                let mut hash_engine = sha256::HashEngine::default();
-                hash_engine.input(&ln.value);
-                hash_engine.input(&ln.sum.to_be_bytes());
-
-                let res = sha256::Hash::from_engine(hash_engine);
-                ln.hash = Some(NodeHash(res.to_byte_array()));
-
-                ln.hash.as_ref().unwrap().clone()
+                ln.node_hash()
             },
 
-            Self::ComputedNode { hash, .. } => {
-                hash.clone()
+            Self::Computed { hash, .. } => {
+                *hash
             },
 
             Self::Nil => NodeHash::default(),
@@ -135,58 +271,550 @@ impl Node {
 
 
     pub fn sum(&self) -> u64 {
-        
+
         match self {
             Self::Branch(bn) => {
-                bn.left.sum() + bn.right.sum()
+                bn.sum
             },
 
             Self::Leaf(ln) => {
                 ln.sum
             },
 
-            Self::ComputedNode { .. } => 0,
+            Self::Computed { sum, .. } => *sum,
             Self::Nil => 0,
         }
     }
 }
 
-#[allow(dead_code)]
+/// An inconsistency found while walking the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Corruption {
+    /// The cached hash doesn't match the one recomputed from the node content.
+    Hash { stored: NodeHash, computed: NodeHash },
+
+    /// The sum of a branch doesn't match the sum of its children.
+    Sum { stored: u64, computed: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptNode {
+    /// Depth of the node, 0 being the root.
+    pub level: usize,
+
+    /// Key bits walked to reach the node, bits past `level` are zero.
+    pub path: NodeHash,
+
+    pub corruption: Corruption,
+}
+
+/// Outcome of [`Tree::verify_integrity`].
+#[derive(Debug, Default)]
+pub struct IntegrityReport {
+    /// Number of leaves visited.
+    pub leaves: usize,
+    pub corrupt: Vec<CorruptNode>,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.corrupt.is_empty()
+    }
+}
+
+/// Reference to a node of the tree arena, `None` standing for an empty
+/// subtree.
+type Link = Option<usize>;
+
+/// Node stored in the tree arena, children are referenced by their index.
+#[derive(Clone)]
+enum Slot {
+    Branch {
+        left: Link,
+        right: Link,
+        sum: u64,
+        hash: OnceLock<NodeHash>,
+    },
+
+    Leaf(LeafNode),
+
+    /// Subtree evicted to a store, only its hash and sum are kept in memory.
+    Pruned {
+        hash: NodeHash,
+        sum: u64,
+    },
+}
+
 pub struct Tree {
+    /// The empty tree, `tree[0]` is the root of an empty tree and
+    /// `tree[depth]` the empty leaf.
     tree: Vec<Node>,
-    root_hash: NodeHash
+
+    /// Non empty nodes of the tree. Nodes are never removed: a replaced
+    /// leaf reuses its slot.
+    nodes: Vec<Slot>,
+    root: Link,
+
+    /// Slots changed since they were last persisted.
+    dirty: HashSet<usize>,
+
+    version: u64,
+    subscribers: Vec<Sender<RootUpdate>>,
 }
 
 impl Tree {
 
     pub fn init() -> Tree {
+        Self::build(MAX_TREE_LEVEL)
+    }
 
-        let mut tree_levels: Vec<Node> = Vec::with_capacity(MAX_TREE_LEVEL + 1);
+    /// Builds a tree only using the first `depth` bits of the keys, so tests
+    /// and fuzzers can exhaustively cover the whole key space. Production
+    /// trees always use [`MAX_TREE_LEVEL`].
+    #[cfg(any(test, feature = "testing"))]
+    pub fn with_depth(depth: usize) -> anyhow::Result<Tree> {
+        if depth == 0 || depth > MAX_TREE_LEVEL {
+            bail!("Invalid tree depth {}, must be within 1..={}", depth, MAX_TREE_LEVEL);
+        }
 
-        tree_levels[MAX_TREE_LEVEL] = Node::Leaf(LeafNode::default());
+        Ok(Self::build(depth))
+    }
 
-        (0..LAST_BIT_INDEX).rev().for_each(|idx| {
+    fn build(depth: usize) -> Tree {
 
-            let branch = BranchNode::new(
+        let mut tree_levels: Vec<Node> = vec![Node::Nil; depth + 1];
+
+        tree_levels[depth] = Node::Computed {
+            hash: Node::Leaf(LeafNode::default()).hash(),
+            sum: 0,
+        };
+
+        (0..depth).rev().for_each(|idx| {
+
+            let branch = Node::Branch(BranchNode::new(
                 tree_levels[idx + 1].clone(),
                 tree_levels[idx + 1].clone()
-            );
+            ));
 
-            tree_levels[idx] = Node::Branch(branch);
+            tree_levels[idx] = Node::Computed { hash: branch.hash(), sum: 0 };
         });
 
-        Tree { 
-            root_hash: tree_levels[0].hash(),
-            tree: tree_levels, 
+        Tree {
+            tree: tree_levels,
+            nodes: Vec::new(),
+            root: None,
+            dirty: HashSet::new(),
+            version: 0,
+            subscribers: Vec::new(),
         }
     }
+
+    /// Number of key bits used to reach a leaf.
+    pub fn depth(&self) -> usize {
+        self.tree.len() - 1
+    }
+
+    pub fn root_hash(&self) -> NodeHash {
+        self.link_hash(self.root, 0)
+    }
+
+    pub fn root_sum(&self) -> u64 {
+        self.link_sum(self.root)
+    }
+
+    fn link_hash(&self, link: Link, level: usize) -> NodeHash {
+        let Some(idx) = link else {
+            return self.tree[level].hash();
+        };
+
+        match &self.nodes[idx] {
+            Slot::Branch { left, right, sum, hash } => *hash.get_or_init(|| {
+                branch_hash(&self.link_hash(*left, level + 1), &self.link_hash(*right, level + 1), *sum)
+            }),
+
+            Slot::Leaf(ln) => ln.node_hash(),
+            Slot::Pruned { hash, .. } => *hash,
+        }
+    }
+
+    fn link_sum(&self, link: Link) -> u64 {
+        match link.map(|idx| &self.nodes[idx]) {
+            Some(Slot::Branch { sum, .. }) => *sum,
+            Some(Slot::Leaf(ln)) => ln.sum,
+            Some(Slot::Pruned { sum, .. }) => *sum,
+            None => 0,
+        }
+    }
+
+    fn children(&self, idx: usize) -> Option<(Link, Link)> {
+        match &self.nodes[idx] {
+            Slot::Branch { left, right, .. } => Some((*left, *right)),
+            Slot::Leaf(_) | Slot::Pruned { .. } => None,
+        }
+    }
+
+    fn alloc(&mut self, slot: Slot) -> usize {
+        self.nodes.push(slot);
+        self.dirty.insert(self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// Points the child of `parent` on the `bit` side, or the root if there
+    /// is no parent, to `link`.
+    fn set_link(&mut self, parent: Option<(usize, u8)>, link: Link) {
+        match parent {
+            None => self.root = link,
+            Some((idx, bit)) => {
+                if let Slot::Branch { left, right, .. } = &mut self.nodes[idx] {
+                    if bit == 0 { *left = link } else { *right = link }
+                }
+            },
+        }
+    }
+
+    /// Inserts a leaf at `key`, replacing the one already there if any.
+    pub fn insert(&mut self, key: &NodeHash, value: [u8; 32], sum: u64) -> anyhow::Result<()> {
+        let old_sum = self.leaf(key).map_or(0, |ln| ln.sum);
+
+        if (self.root_sum() - old_sum).checked_add(sum).is_none() {
+            bail!("Inserting leaf {} overflows the root sum", key);
+        }
+
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+
+        // Walk down to the leaf, creating the missing branches.
+        let mut path = Vec::with_capacity(self.depth());
+        let mut parent = None;
+        let mut current = self.root;
+
+        for level in 0..self.depth() {
+            let idx = match current {
+                Some(idx) => idx,
+                None => {
+                    let idx = self.alloc(Slot::Branch { left: None, right: None, sum: 0, hash: OnceLock::new() });
+                    self.set_link(parent, Some(idx));
+                    idx
+                },
+            };
+
+            let bit = key.bit(level);
+            let Some((left, right)) = self.children(idx) else {
+                bail!("Cannot insert {} below the pruned subtree at level {}", key, level);
+            };
+
+            current = if bit == 0 { left } else { right };
+            parent = Some((idx, bit));
+            path.push(idx);
+        }
+
+        let leaf = Slot::Leaf(LeafNode::new(value, sum));
+        match current {
+            Some(idx) => {
+                self.nodes[idx] = leaf;
+                self.dirty.insert(idx);
+            },
+            None => {
+                let idx = self.alloc(leaf);
+                self.set_link(parent, Some(idx));
+            },
+        }
+
+        // Update the sums and reset the cached hashes on the way back up.
+        for idx in path.into_iter().rev() {
+            let (left, right) = self.children(idx).expect("path only holds branches");
+            let new_sum = self.link_sum(left) + self.link_sum(right);
+
+            if let Slot::Branch { sum, hash, .. } = &mut self.nodes[idx] {
+                *sum = new_sum;
+                hash.take();
+            }
+            self.dirty.insert(idx);
+        }
+
+        #[cfg(feature = "metrics")]
+        metrics::record_insert(start.elapsed());
+
+        self.publish_root();
+
+        Ok(())
+    }
+
+    fn leaf(&self, key: &NodeHash) -> Option<&LeafNode> {
+        let mut link = self.root;
+
+        for level in 0..self.depth() {
+            let (left, right) = self.children(link?)?;
+            link = if key.bit(level) == 0 { left } else { right };
+        }
+
+        match &self.nodes[link?] {
+            Slot::Leaf(ln) => Some(ln),
+            Slot::Branch { .. } | Slot::Pruned { .. } => None,
+        }
+    }
+
+    /// Walks the whole tree recomputing every hash and sum from the leaves,
+    /// and reports the nodes whose stored values don't match.
+    pub fn verify_integrity(&self) -> IntegrityReport {
+        let mut report = IntegrityReport::default();
+        self.check(self.root, 0, &mut NodeHash::default(), &mut report);
+
+        metrics::record_integrity_failures(report.corrupt.len());
+
+        report
+    }
+
+    fn check(&self, link: Link, level: usize, path: &mut NodeHash, report: &mut IntegrityReport) -> (NodeHash, u64) {
+
+        let Some(idx) = link else {
+            return (self.tree[level].hash(), 0);
+        };
+
+        match &self.nodes[idx] {
+            Slot::Branch { left, right, sum: stored_sum, hash: stored_hash } => {
+                let (left_hash, left_sum) = self.check(*left, level + 1, path, report);
+
+                path.set_bit(level);
+                let (right_hash, right_sum) = self.check(*right, level + 1, path, report);
+                path.clear_bit(level);
+
+                let sum = left_sum.saturating_add(right_sum);
+                if *stored_sum != sum {
+                    report.corrupt.push(CorruptNode {
+                        level,
+                        path: *path,
+                        corruption: Corruption::Sum { stored: *stored_sum, computed: sum },
+                    });
+                }
+
+                let hash = branch_hash(&left_hash, &right_hash, sum);
+                if let Some(stored) = stored_hash.get() && *stored != hash {
+                    report.corrupt.push(CorruptNode {
+                        level,
+                        path: *path,
+                        corruption: Corruption::Hash { stored: *stored, computed: hash },
+                    });
+                }
+
+                (hash, sum)
+            },
+
+            Slot::Leaf(ln) => {
+                report.leaves += 1;
+
+                let hash = leaf_hash(&ln.value, ln.sum);
+                if let Some(stored) = ln.hash.get() && *stored != hash {
+                    report.corrupt.push(CorruptNode {
+                        level,
+                        path: *path,
+                        corruption: Corruption::Hash { stored: *stored, computed: hash },
+                    });
+                }
+
+                (hash, ln.sum)
+            },
+
+            Slot::Pruned { hash, sum } => (*hash, *sum),
+        }
+    }
+
+    /// Verifies the tree and, if anything is corrupt, rebuilds it from its
+    /// leaves. Returns the report of the verification done before repairing.
+    pub fn repair(&mut self) -> anyhow::Result<IntegrityReport> {
+        let report = self.verify_integrity();
+
+        if report.is_ok() {
+            return Ok(report);
+        }
+
+        let mut leaves = Vec::with_capacity(report.leaves);
+        self.collect_leaves(self.root, 0, &mut NodeHash::default(), &mut leaves)?;
+
+        let mut rebuilt = Self::build(self.depth());
+        for (key, leaf) in leaves {
+            rebuilt.insert(&key, leaf.value, leaf.sum)?;
+        }
+
+        rebuilt.version = self.version;
+        rebuilt.subscribers = std::mem::take(&mut self.subscribers);
+        *self = rebuilt;
+
+        self.publish_root();
+
+        Ok(report)
+    }
+
+    fn collect_leaves(&self, link: Link, level: usize, path: &mut NodeHash, leaves: &mut Vec<(NodeHash, LeafNode)>) -> anyhow::Result<()> {
+
+        match link.map(|idx| &self.nodes[idx]) {
+            Some(Slot::Branch { left, right, .. }) => {
+                self.collect_leaves(*left, level + 1, path, leaves)?;
+
+                path.set_bit(level);
+                self.collect_leaves(*right, level + 1, path, leaves)?;
+                path.clear_bit(level);
+            },
+
+            Some(Slot::Leaf(ln)) => leaves.push((*path, ln.clone())),
+
+            Some(Slot::Pruned { .. }) => bail!("Cannot rebuild the pruned subtree at level {} from leaves", level),
+
+            None => {},
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tree::Tree;
+    use bitcoin::hashes::{Hash, sha256};
+
+    use crate::tree::{Corruption, MAX_TREE_LEVEL, NodeHash, Slot, Tree};
+
+    fn key(b: u8) -> NodeHash {
+        let mut k = [0; 32];
+        k[0] = b;
+        k[31] = b.wrapping_mul(7);
+        NodeHash(k)
+    }
 
+    #[test]
+    fn node_hash_constructors() {
+        let hash = NodeHash::from_slice(&[3; 32]).unwrap();
+        assert_eq!(hash, NodeHash::new([3; 32]));
+        assert_eq!(hash.as_bytes(), &[3; 32]);
+        assert_eq!(hash.to_hex().parse::<NodeHash>().unwrap(), hash);
+        assert!(NodeHash::from_slice(&[3; 31]).is_err());
+
+        let digest = sha256::Hash::hash(b"key");
+        assert_eq!(NodeHash::from(digest).as_bytes(), digest.as_byte_array());
+        assert_eq!(sha256::Hash::from(NodeHash::from(digest)), digest);
+        assert_eq!(<[u8; 32]>::from(hash), [3; 32]);
+        assert_eq!(NodeHash::try_from(&[3u8; 32][..]), Ok(hash));
+        assert!(NodeHash::try_from(&[3u8; 33][..]).is_err());
+
+        let json = serde_json::to_string(&hash).unwrap();
+        assert_eq!(json, format!("\"{}\"", "03".repeat(32)));
+        assert_eq!(serde_json::from_str::<NodeHash>(&json).unwrap(), hash);
+    }
+
+    #[test]
     fn new_tree() {
-        let _ms_tree = Tree::init();
+        let ms_tree = Tree::init();
+
+        assert_eq!(ms_tree.root_sum(), 0);
+        assert_eq!(ms_tree.root_hash(), ms_tree.tree[0].hash());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn insert_updates_root() {
+        let mut ms_tree = Tree::init();
+        let empty_root = ms_tree.root_hash();
+
+        ms_tree.insert(&key(1), [1; 32], 10).unwrap();
+        ms_tree.insert(&key(2), [2; 32], 20).unwrap();
+        assert_eq!(ms_tree.root_sum(), 30);
+        assert_ne!(ms_tree.root_hash(), empty_root);
+
+        // Replacing a leaf only accounts for the new sum, and reuses its slot.
+        let nodes = ms_tree.nodes.len();
+        ms_tree.insert(&key(1), [1; 32], 5).unwrap();
+        assert_eq!(ms_tree.root_sum(), 25);
+        assert_eq!(ms_tree.nodes.len(), nodes);
+
+        assert!(ms_tree.insert(&key(3), [3; 32], u64::MAX).is_err());
+        assert_eq!(ms_tree.root_sum(), 25);
+    }
+
+    #[test]
+    fn concurrent_reads() {
+        let mut ms_tree = Tree::init();
+        for i in 0..4 {
+            ms_tree.insert(&key(i), [i; 32], 1).unwrap();
+        }
+
+        // Hashes are lazily computed through a shared reference.
+        let roots: Vec<NodeHash> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4).map(|_| s.spawn(|| ms_tree.root_hash())).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert!(roots.iter().all(|root| *root == ms_tree.root_hash()));
+    }
+
+    #[test]
+    fn insertion_order_does_not_matter() {
+        let mut a = Tree::init();
+        let mut b = Tree::init();
+
+        for i in 0..8 {
+            a.insert(&key(i), [i; 32], i as u64).unwrap();
+            b.insert(&key(7 - i), [7 - i; 32], (7 - i) as u64).unwrap();
+        }
+
+        assert_eq!(a.root_hash(), b.root_hash());
+    }
+
+    #[test]
+    fn reduced_depth() {
+        assert!(Tree::with_depth(0).is_err());
+        assert!(Tree::with_depth(MAX_TREE_LEVEL + 1).is_err());
+        assert_eq!(Tree::with_depth(MAX_TREE_LEVEL).unwrap().root_hash(), Tree::init().root_hash());
+    }
+
+    #[test]
+    fn exhaustive_depth_8() {
+        let mut forward = Tree::with_depth(8).unwrap();
+        let mut backward = Tree::with_depth(8).unwrap();
+
+        for i in 0..=255u8 {
+            forward.insert(&NodeHash([i; 32]), [i; 32], i as u64).unwrap();
+            backward.insert(&NodeHash([255 - i; 32]), [255 - i; 32], (255 - i) as u64).unwrap();
+        }
+
+        assert_eq!(forward.root_sum(), (0..=255).sum::<u64>());
+        assert_eq!(forward.root_hash(), backward.root_hash());
+
+        let report = forward.verify_integrity();
+        assert!(report.is_ok());
+        assert_eq!(report.leaves, 256);
+
+        // Only the first 8 bits are used, so this replaces the leaf at key 1.
+        let mut key = [0; 32];
+        key[0] = 1;
+        forward.insert(&NodeHash(key), [0; 32], 0).unwrap();
+        assert_eq!(forward.root_sum(), (0..=255).sum::<u64>() - 1);
+        assert_eq!(forward.verify_integrity().leaves, 256);
+    }
+
+    #[test]
+    fn verify_and_repair() {
+        let mut ms_tree = Tree::init();
+        for i in 0..4 {
+            ms_tree.insert(&key(i), [i; 32], 100).unwrap();
+        }
+        let root = ms_tree.root_hash();
+
+        let report = ms_tree.verify_integrity();
+        assert!(report.is_ok());
+        assert_eq!(report.leaves, 4);
+
+        // Corrupt the sum stored in the root's left child.
+        let Some((Some(left), _)) = ms_tree.children(ms_tree.root.unwrap()) else { panic!("root has no left branch") };
+        let Slot::Branch { sum, .. } = &mut ms_tree.nodes[left] else { panic!("left is not a branch") };
+        *sum += 1;
+
+        let report = ms_tree.verify_integrity();
+        assert!(!report.is_ok());
+        assert_eq!(report.corrupt[0].level, 1);
+        assert_eq!(report.corrupt[0].corruption, Corruption::Sum { stored: 201, computed: 200 });
+
+        let report = ms_tree.repair().unwrap();
+        assert!(!report.is_ok());
+        assert!(ms_tree.verify_integrity().is_ok());
+        assert_eq!(ms_tree.root_hash(), root);
+        assert_eq!(ms_tree.root_sum(), 400);
+    }
+}
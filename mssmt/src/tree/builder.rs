@@ -0,0 +1,110 @@
+use anyhow::bail;
+
+use super::{BranchNode, LeafNode, MAX_TREE_LEVEL, Node, NodeHash};
+
+/// Builds a subtree bottom up: starting from a leaf at `key`, each call to
+/// [`NodeBuilder::sibling`] adds the sibling of the current node and moves
+/// one level up, the side of the sibling being given by the key bits.
+///
+/// Adding the siblings of every level yields the root of the tree, which is
+/// how a merkle proof for a leaf is checked.
+pub struct NodeBuilder {
+    key: NodeHash,
+    level: usize,
+    node: Node,
+}
+
+impl NodeBuilder {
+    /// Starts from `leaf` at `key` in a tree of `depth` levels, that is
+    /// [`MAX_TREE_LEVEL`] for production trees or [`Tree::depth`].
+    ///
+    /// [`Tree::depth`]: super::Tree::depth
+    pub fn from_leaf(key: NodeHash, leaf: LeafNode, depth: usize) -> anyhow::Result<Self> {
+        if depth == 0 || depth > MAX_TREE_LEVEL {
+            bail!("Invalid tree depth {}, must be within 1..={}", depth, MAX_TREE_LEVEL);
+        }
+
+        Ok(NodeBuilder { key, level: depth, node: Node::Leaf(leaf) })
+    }
+
+    /// Level of the node built so far, 0 being the root.
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn sibling(self, sibling: Node) -> anyhow::Result<Self> {
+        let NodeBuilder { key, level, node } = self;
+
+        if level == 0 {
+            bail!("Already at the root, can't add another sibling");
+        }
+
+        let level = level - 1;
+        let branch = if key.bit(level) == 0 {
+            BranchNode::from_children(node, sibling)?
+        } else {
+            BranchNode::from_children(sibling, node)?
+        };
+
+        Ok(NodeBuilder { key, level, node: Node::Branch(branch) })
+    }
+
+    pub fn build(self) -> Node {
+        self.node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tree::{BranchNode, LeafNode, MAX_TREE_LEVEL, Node, NodeBuilder, NodeHash, Tree};
+
+    /// Builds the root of a tree holding a single leaf at `key`, every sibling
+    /// then being an empty subtree.
+    fn single_leaf_root(ms_tree: &Tree, key: NodeHash) -> NodeBuilder {
+        let mut builder = NodeBuilder::from_leaf(key, LeafNode::new([1; 32], 42), ms_tree.depth()).unwrap();
+        for level in (1..=ms_tree.depth()).rev() {
+            builder = builder.sibling(ms_tree.tree[level].clone()).unwrap();
+        }
+        builder
+    }
+
+    #[test]
+    fn build_root_from_leaf() {
+        let key = NodeHash::new([0x5a; 32]);
+
+        let mut ms_tree = Tree::init();
+        ms_tree.insert(&key, [1; 32], 42).unwrap();
+
+        let builder = single_leaf_root(&ms_tree, key);
+        assert_eq!(builder.level(), 0);
+        assert!(builder.sibling(Node::Nil).is_err());
+
+        let root = single_leaf_root(&ms_tree, key).build();
+        assert_eq!(root.sum(), 42);
+        assert_eq!(root.hash(), ms_tree.root_hash());
+
+        // Shallower trees stop adding siblings at their own depth.
+        let mut shallow = Tree::with_depth(8).unwrap();
+        shallow.insert(&key, [1; 32], 42).unwrap();
+        let builder = single_leaf_root(&shallow, key);
+        assert_eq!(builder.level(), 0);
+        assert_eq!(builder.build().hash(), shallow.root_hash());
+
+        assert!(NodeBuilder::from_leaf(key, LeafNode::default(), 0).is_err());
+        assert!(NodeBuilder::from_leaf(key, LeafNode::default(), MAX_TREE_LEVEL + 1).is_err());
+    }
+
+    #[test]
+    fn sum_overflow() {
+        let leaf = Node::Leaf(LeafNode::new([0; 32], u64::MAX));
+        let computed = Node::Computed { hash: NodeHash::default(), sum: 1 };
+
+        assert!(BranchNode::from_children(leaf.clone(), computed.clone()).is_err());
+        assert!(NodeBuilder::from_leaf(NodeHash::default(), LeafNode::new([0; 32], u64::MAX), MAX_TREE_LEVEL).unwrap().sibling(computed).is_err());
+
+        let branch = BranchNode::from_children(leaf.clone(), Node::Nil).unwrap();
+        assert_eq!(branch.sum(), u64::MAX);
+        assert_eq!(branch.left_hash(), leaf.hash());
+        assert_eq!(branch.right_hash(), Node::Nil.hash());
+    }
+}
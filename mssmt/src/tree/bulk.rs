@@ -0,0 +1,169 @@
+//! Bottom-up construction of a tree from a known set of leaves, much faster
+//! than inserting them one by one for the initial import of a universe.
+//!
+//! [`Tree::import`] streams the result into a store instead of keeping it
+//! in memory: each subtree rooted at the batch level is written as soon as
+//! it is built and its slots are reused for the next one, so only the
+//! branches above that level stay allocated.
+
+use std::sync::OnceLock;
+
+use anyhow::{bail, Context};
+
+use super::{LeafNode, Link, NodeHash, Slot, Tree};
+use crate::store::KvStore;
+
+impl Tree {
+    /// Builds the tree holding `leaves`, the last one given winning for
+    /// duplicate keys. Leaves are sorted in path order and every node is
+    /// allocated once, its hash being computed on first use. Persist the
+    /// result with [`Tree::persist`].
+    pub fn from_leaves(leaves: Vec<(NodeHash, [u8; 32], u64)>) -> anyhow::Result<Tree> {
+        let mut tree = Tree::init();
+        tree.bulk_insert(leaves)?;
+
+        Ok(tree)
+    }
+
+    /// Builds the tree holding `leaves` straight into `kv`, writing each
+    /// subtree rooted at `batch_level` before building the next one, and
+    /// records its root as the latest one. Returns that root, the tree
+    /// reopens from it with [`Tree::load`] or a hybrid tree.
+    pub fn import<S: KvStore>(leaves: Vec<(NodeHash, [u8; 32], u64)>, kv: &mut S, batch_level: usize) -> anyhow::Result<NodeHash> {
+        let mut tree = Tree::init();
+        if batch_level > tree.depth() {
+            bail!("Batch level {} is deeper than the tree depth {}", batch_level, tree.depth());
+        }
+
+        let unique = sorted_unique(leaves);
+        tree.root = tree.build_streamed(&unique, 0, batch_level, kv)?;
+
+        tree.persist(kv)
+    }
+
+    fn bulk_insert(&mut self, leaves: Vec<(NodeHash, [u8; 32], u64)>) -> anyhow::Result<()> {
+        let unique = sorted_unique(leaves);
+
+        self.nodes.reserve(unique.len() * 2);
+        self.root = self.build_link(&unique, 0)?;
+
+        Ok(())
+    }
+
+    fn build_streamed<S: KvStore>(&mut self, leaves: &[(NodeHash, [u8; 32], u64)], level: usize, batch_level: usize, kv: &mut S) -> anyhow::Result<Link> {
+        if leaves.is_empty() {
+            return Ok(None);
+        }
+
+        if level == batch_level {
+            let start = self.nodes.len();
+            let link = self.build_link(leaves, level)?;
+            let hash = self.persist_link(link, level, kv)?;
+
+            // Zero leaves hash like empty ones, such a subtree can't be told
+            // apart from an absent one once pruned so it stays allocated.
+            if hash == self.tree[level].hash() {
+                return Ok(link);
+            }
+
+            let sum = self.link_sum(link);
+            self.nodes.truncate(start);
+
+            return Ok(Some(self.alloc_clean(Slot::Pruned { hash, sum })));
+        }
+
+        let split = leaves.partition_point(|(key, _, _)| key.bit(level) == 0);
+        let left = self.build_streamed(&leaves[..split], level + 1, batch_level, kv)?;
+        let right = self.build_streamed(&leaves[split..], level + 1, batch_level, kv)?;
+
+        let sum = self.link_sum(left).checked_add(self.link_sum(right)).context("Sum overflow")?;
+
+        Ok(Some(self.alloc(Slot::Branch { left, right, sum, hash: OnceLock::new() })))
+    }
+
+    fn build_link(&mut self, leaves: &[(NodeHash, [u8; 32], u64)], level: usize) -> anyhow::Result<Link> {
+        let Some((_, value, sum)) = leaves.first() else {
+            return Ok(None);
+        };
+
+        if level == self.depth() {
+            return Ok(Some(self.alloc(Slot::Leaf(LeafNode::new(*value, *sum)))));
+        }
+
+        let split = leaves.partition_point(|(key, _, _)| key.bit(level) == 0);
+        let left = self.build_link(&leaves[..split], level + 1)?;
+        let right = self.build_link(&leaves[split..], level + 1)?;
+
+        let sum = self.link_sum(left).checked_add(self.link_sum(right)).context("Sum overflow")?;
+
+        Ok(Some(self.alloc(Slot::Branch { left, right, sum, hash: OnceLock::new() })))
+    }
+}
+
+/// Sorts `leaves` in path order, keeping the last one given for duplicate
+/// keys.
+fn sorted_unique(mut leaves: Vec<(NodeHash, [u8; 32], u64)>) -> Vec<(NodeHash, [u8; 32], u64)> {
+    // Comparing bytes with their bits reversed compares keys bit by bit in
+    // the order the path uses them.
+    leaves.sort_by_key(|(key, _, _)| key.0.map(u8::reverse_bits));
+
+    let mut unique: Vec<(NodeHash, [u8; 32], u64)> = Vec::with_capacity(leaves.len());
+    for leaf in leaves {
+        match unique.last_mut() {
+            Some(last) if last.0 == leaf.0 => *last = leaf,
+            _ => unique.push(leaf),
+        }
+    }
+
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        store::{KvStore, MemoryKv},
+        testing::corpus::{Corpus, KeyDistribution},
+        tree::{NodeHash, Tree, hybrid::HybridTree, persist::ROOT_KEY},
+    };
+
+    #[test]
+    fn matches_inserts() {
+        let mut leaves = Corpus::new(1).leaves(KeyDistribution::Clustered { clusters: 4, prefix_bits: 12 }, 300, 1_000);
+        leaves.push((leaves[0].0, [9; 32], 5));
+
+        let mut ms_tree = Tree::init();
+        for (key, value, sum) in &leaves {
+            ms_tree.insert(key, *value, *sum).unwrap();
+        }
+
+        let bulk = Tree::from_leaves(leaves).unwrap();
+        assert_eq!(bulk.root_hash(), ms_tree.root_hash());
+        assert_eq!(bulk.root_sum(), ms_tree.root_sum());
+        assert_eq!(bulk.stats(), ms_tree.stats());
+        assert!(bulk.verify_integrity().is_ok());
+
+        assert_eq!(Tree::from_leaves(Vec::new()).unwrap().root_hash(), Tree::init().root_hash());
+        assert!(Tree::from_leaves(vec![(NodeHash([1; 32]), [0; 32], u64::MAX), (NodeHash([2; 32]), [0; 32], 1)]).is_err());
+    }
+
+    #[test]
+    fn streams_into_the_store() {
+        let leaves = Corpus::new(2).leaves(KeyDistribution::Clustered { clusters: 4, prefix_bits: 12 }, 100, 1_000);
+        let bulk = Tree::from_leaves(leaves.clone()).unwrap();
+
+        let mut kv = MemoryKv::default();
+        let root = Tree::import(leaves.clone(), &mut kv, 8).unwrap();
+        assert_eq!(root, bulk.root_hash());
+        assert_eq!(kv.get(ROOT_KEY).unwrap().unwrap(), root.as_bytes());
+
+        let loaded = Tree::load(&kv).unwrap();
+        assert_eq!(loaded.root_sum(), bulk.root_sum());
+        assert_eq!(loaded.stats(), bulk.stats());
+
+        let mut hybrid = HybridTree::new(kv, 8, 2).unwrap();
+        assert_eq!(hybrid.root_hash(), root);
+        assert_eq!(hybrid.get(&leaves[0].0).unwrap().map(|ln| ln.value), Some(leaves[0].1));
+
+        assert!(Tree::import(leaves, &mut MemoryKv::default(), 257).is_err());
+    }
+}
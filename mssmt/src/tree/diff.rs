@@ -0,0 +1,106 @@
+//! Leaf level differences between two trees.
+
+use anyhow::bail;
+
+use super::{LeafNode, Link, NodeHash, Slot, Tree};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LeafDiff {
+    Added { key: NodeHash, leaf: LeafNode },
+    Removed { key: NodeHash, leaf: LeafNode },
+    Changed { key: NodeHash, old: LeafNode, new: LeafNode },
+}
+
+/// Returns the leaves added, removed or changed going from `a` to `b`.
+/// Subtrees with the same hash are skipped so the cost is proportional to the
+/// difference, not to the trees size. Roots loaded from a store are compared
+/// by loading them first, see [`Tree::load_root`].
+pub fn diff_trees(a: &Tree, b: &Tree) -> anyhow::Result<Vec<LeafDiff>> {
+    if a.depth() != b.depth() {
+        bail!("Cannot diff trees of depth {} and {}", a.depth(), b.depth());
+    }
+
+    let mut diffs = Vec::new();
+    diff_links(a, a.root, b, b.root, 0, &mut NodeHash::default(), &mut diffs)?;
+
+    Ok(diffs)
+}
+
+fn diff_links(a: &Tree, link_a: Link, b: &Tree, link_b: Link, level: usize, path: &mut NodeHash, diffs: &mut Vec<LeafDiff>) -> anyhow::Result<()> {
+    if a.link_hash(link_a, level) == b.link_hash(link_b, level) {
+        return Ok(());
+    }
+
+    if level == a.depth() {
+        let leaf_a = link_a.and_then(|idx| leaf_slot(a, idx));
+        let leaf_b = link_b.and_then(|idx| leaf_slot(b, idx));
+        let key = *path;
+
+        match (leaf_a, leaf_b) {
+            (Some(old), Some(new)) => diffs.push(LeafDiff::Changed { key, old, new }),
+            (Some(leaf), None) => diffs.push(LeafDiff::Removed { key, leaf }),
+            (None, Some(leaf)) => diffs.push(LeafDiff::Added { key, leaf }),
+            (None, None) => {},
+        }
+
+        return Ok(());
+    }
+
+    let (left_a, right_a) = children(a, link_a, level)?;
+    let (left_b, right_b) = children(b, link_b, level)?;
+
+    diff_links(a, left_a, b, left_b, level + 1, path, diffs)?;
+
+    path.set_bit(level);
+    diff_links(a, right_a, b, right_b, level + 1, path, diffs)?;
+    path.clear_bit(level);
+
+    Ok(())
+}
+
+fn leaf_slot(tree: &Tree, idx: usize) -> Option<LeafNode> {
+    match &tree.nodes[idx] {
+        Slot::Leaf(ln) => Some(ln.clone()),
+        _ => None,
+    }
+}
+
+fn children(tree: &Tree, link: Link, level: usize) -> anyhow::Result<(Link, Link)> {
+    match link {
+        None => Ok((None, None)),
+        Some(idx) => match tree.children(idx) {
+            Some(children) => Ok(children),
+            None => bail!("Cannot diff the pruned subtree at level {}", level),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tree::{LeafNode, NodeHash, Tree};
+
+    use super::{LeafDiff, diff_trees};
+
+    #[test]
+    fn diff_leaves() {
+        let mut a = Tree::init();
+        let mut b = Tree::init();
+        for i in 1..=4u8 {
+            a.insert(&NodeHash([i; 32]), [i; 32], i as u64).unwrap();
+            b.insert(&NodeHash([i; 32]), [i; 32], i as u64).unwrap();
+        }
+        assert!(diff_trees(&a, &b).unwrap().is_empty());
+
+        b.insert(&NodeHash([2; 32]), [2; 32], 20).unwrap();
+        b.insert(&NodeHash([5; 32]), [5; 32], 5).unwrap();
+        a.insert(&NodeHash([6; 32]), [6; 32], 6).unwrap();
+
+        let diffs = diff_trees(&a, &b).unwrap();
+        assert_eq!(diffs.len(), 3);
+        assert!(diffs.contains(&LeafDiff::Changed { key: NodeHash([2; 32]), old: LeafNode::new([2; 32], 2), new: LeafNode::new([2; 32], 20) }));
+        assert!(diffs.contains(&LeafDiff::Added { key: NodeHash([5; 32]), leaf: LeafNode::new([5; 32], 5) }));
+        assert!(diffs.contains(&LeafDiff::Removed { key: NodeHash([6; 32]), leaf: LeafNode::new([6; 32], 6) }));
+
+        assert!(diff_trees(&a, &Tree::with_depth(8).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+//! Tree keeping only its most recently used subtrees in memory.
+//!
+//! Every branch above `level` always stays resident, subtrees rooted at
+//! `level` are evicted to the store once more than `max_resident` of them
+//! are loaded, least recently used first, and loaded back on their next
+//! access. Hot universes get the speed of the in memory tree while cold ones
+//! only cost their node hashes.
+//!
+//! Subtrees holding only zero leaves are the exception: they hash like empty
+//! ones and can't be loaded back once pruned, so they stay in memory without
+//! counting against `max_resident`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::{bail, Context};
+
+use super::{LeafNode, Link, NodeHash, Slot, Tree, persist::ROOT_KEY};
+use crate::store::KvStore;
+
+pub struct HybridTree<S: KvStore> {
+    tree: Tree,
+    kv: S,
+    level: usize,
+    max_resident: usize,
+
+    /// Last access tick of each resident subtree, by key prefix.
+    resident: HashMap<NodeHash, u64>,
+
+    /// Key prefixes of the resident subtrees, least recently used first.
+    lru: BTreeMap<u64, NodeHash>,
+    tick: u64,
+
+    /// Arena slots no longer reachable from the root.
+    garbage: usize,
+}
+
+impl<S: KvStore> HybridTree<S> {
+    /// Opens the tree last flushed to `kv`, or an empty one, keeping at most
+    /// `max_resident` subtrees rooted at `level` in memory.
+    pub fn new(kv: S, level: usize, max_resident: usize) -> anyhow::Result<Self> {
+        let tree = match kv.get(ROOT_KEY)? {
+            Some(root) => {
+                let root = NodeHash::from_slice(&root).map_err(anyhow::Error::msg)?;
+                Tree::load_pruned(&kv, &root, level).context("Failed to load the flushed root")?
+            },
+            None => Tree::init(),
+        };
+
+        if level > tree.depth() {
+            bail!("Eviction level {} is deeper than the tree depth {}", level, tree.depth());
+        }
+
+        Ok(HybridTree { tree, kv, level, max_resident, resident: HashMap::new(), lru: BTreeMap::new(), tick: 0, garbage: 0 })
+    }
+
+    pub fn root_hash(&self) -> NodeHash {
+        self.tree.root_hash()
+    }
+
+    pub fn root_sum(&self) -> u64 {
+        self.tree.root_sum()
+    }
+
+    /// Number of subtrees currently held in memory, not counting the ones
+    /// holding only zero leaves.
+    pub fn resident(&self) -> usize {
+        self.resident.len()
+    }
+
+    /// Inserts a leaf at `key`, loading its subtree back first if it was
+    /// evicted.
+    pub fn insert(&mut self, key: &NodeHash, value: [u8; 32], sum: u64) -> anyhow::Result<()> {
+        self.touch(key)?;
+        self.tree.insert(key, value, sum)?;
+        self.evict()
+    }
+
+    /// Returns the leaf at `key`, loading its subtree back first if it was
+    /// evicted.
+    pub fn get(&mut self, key: &NodeHash) -> anyhow::Result<Option<LeafNode>> {
+        self.touch(key)?;
+        let leaf = self.tree.leaf(key).cloned();
+        self.evict()?;
+
+        Ok(leaf)
+    }
+
+    /// Writes the resident nodes changed since the last flush to the store
+    /// and records the root, the tree reopens from that root with
+    /// [`HybridTree::new`].
+    pub fn flush(&mut self) -> anyhow::Result<NodeHash> {
+        self.tree.persist(&mut self.kv)
+    }
+
+    pub fn into_inner(self) -> S {
+        self.kv
+    }
+
+    fn prefix(&self, key: &NodeHash) -> NodeHash {
+        let mut prefix = *key;
+        for idx in self.level..self.tree.depth() {
+            prefix.clear_bit(idx);
+        }
+        prefix
+    }
+
+    /// Marks the subtree of `key` as the most recently used one, loading it
+    /// from the store if it was evicted.
+    fn touch(&mut self, key: &NodeHash) -> anyhow::Result<()> {
+        let prefix = self.prefix(key);
+
+        if let Some(idx) = self.tree.slot_at(&prefix, self.level)
+            && let Slot::Pruned { hash, .. } = self.tree.nodes[idx]
+        {
+            let loaded = self.tree.load_link(&self.kv, self.level, &hash, None)?.context("Evicted an empty subtree")?;
+            self.tree.nodes.swap(idx, loaded);
+            self.garbage += 1;
+        }
+
+        self.tick += 1;
+        if let Some(tick) = self.resident.insert(prefix, self.tick) {
+            self.lru.remove(&tick);
+        }
+        self.lru.insert(self.tick, prefix);
+
+        Ok(())
+    }
+
+    fn evict(&mut self) -> anyhow::Result<()> {
+        while self.resident.len() > self.max_resident {
+            let Some((_, prefix)) = self.lru.pop_first() else {
+                break;
+            };
+            self.resident.remove(&prefix);
+
+            let Some(idx) = self.tree.slot_at(&prefix, self.level) else {
+                continue;
+            };
+
+            // Zero leaves hash like empty ones, such a subtree can't be told
+            // apart from an absent one once pruned so it stays resident.
+            if self.tree.link_hash(Some(idx), self.level) == self.tree.tree[self.level].hash() {
+                continue;
+            }
+
+            let hash = self.tree.persist_link(Some(idx), self.level, &mut self.kv)?;
+            let sum = self.tree.link_sum(Some(idx));
+            self.garbage += self.tree.subtree_len(Some(idx)) - 1;
+            self.tree.nodes[idx] = Slot::Pruned { hash, sum };
+        }
+
+        if self.garbage > self.tree.nodes.len() / 2 {
+            self.tree.compact();
+            self.garbage = 0;
+        }
+
+        Ok(())
+    }
+}
+
+impl Tree {
+    /// Returns the node `level` bits down the path of `key`.
+    fn slot_at(&self, key: &NodeHash, level: usize) -> Link {
+        let mut link = self.root;
+
+        for idx in 0..level {
+            let (left, right) = self.children(link?)?;
+            link = if key.bit(idx) == 0 { left } else { right };
+        }
+
+        link
+    }
+
+    fn subtree_len(&self, link: Link) -> usize {
+        match link.map(|idx| &self.nodes[idx]) {
+            Some(Slot::Branch { left, right, .. }) => 1 + self.subtree_len(*left) + self.subtree_len(*right),
+            Some(_) => 1,
+            None => 0,
+        }
+    }
+
+    /// Drops the arena slots no longer reachable from the root.
+    fn compact(&mut self) {
+        let mut nodes = Vec::with_capacity(self.nodes.len());
+        let mut dirty = HashSet::with_capacity(self.dirty.len());
+        self.root = self.move_link(self.root, &mut nodes, &mut dirty);
+        self.nodes = nodes;
+        self.dirty = dirty;
+    }
+
+    fn move_link(&mut self, link: Link, nodes: &mut Vec<Slot>, dirty: &mut HashSet<usize>) -> Link {
+        let idx = link?;
+        let mut slot = std::mem::replace(&mut self.nodes[idx], Slot::Pruned { hash: NodeHash::default(), sum: 0 });
+
+        if let Slot::Branch { left, right, .. } = &mut slot {
+            *left = self.move_link(*left, nodes, dirty);
+            *right = self.move_link(*right, nodes, dirty);
+        }
+
+        nodes.push(slot);
+        if self.dirty.contains(&idx) {
+            dirty.insert(nodes.len() - 1);
+        }
+        Some(nodes.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{store::MemoryKv, tree::{NodeHash, Slot, Tree}};
+
+    use super::HybridTree;
+
+    #[test]
+    fn evicts_and_reloads_subtrees() {
+        let mut hybrid = HybridTree::new(MemoryKv::default(), 4, 2).unwrap();
+        let mut ms_tree = Tree::init();
+
+        // The low nibble of the first byte picks one of 16 subtrees.
+        for round in 1..=3u64 {
+            for b in 0..16u8 {
+                let key = NodeHash([b; 32]);
+                hybrid.insert(&key, [b; 32], round * b as u64).unwrap();
+                ms_tree.insert(&key, [b; 32], round * b as u64).unwrap();
+
+                assert_eq!(hybrid.root_hash(), ms_tree.root_hash());
+                assert!(hybrid.resident() <= 2);
+            }
+        }
+        assert_eq!(hybrid.root_sum(), ms_tree.root_sum());
+        assert!(hybrid.tree.verify_integrity().is_ok());
+        assert!(hybrid.tree.nodes.len() < ms_tree.nodes.len());
+
+        assert_eq!(hybrid.get(&NodeHash([3; 32])).unwrap().unwrap().sum(), 9);
+        assert!(hybrid.get(&NodeHash([0x13; 32])).unwrap().is_none());
+
+        let root = hybrid.flush().unwrap();
+        let kv = hybrid.into_inner();
+        assert_eq!(Tree::load(&kv).unwrap().root_hash(), root);
+
+        let mut reopened = HybridTree::new(kv, 4, 2).unwrap();
+        assert_eq!(reopened.root_hash(), root);
+        assert_eq!(reopened.resident(), 0);
+
+        reopened.insert(&NodeHash([5; 32]), [0; 32], 1).unwrap();
+        ms_tree.insert(&NodeHash([5; 32]), [0; 32], 1).unwrap();
+        assert_eq!(reopened.root_hash(), ms_tree.root_hash());
+        assert_eq!(reopened.root_sum(), ms_tree.root_sum());
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut hybrid = HybridTree::new(MemoryKv::default(), 4, 2).unwrap();
+        let pruned = |hybrid: &HybridTree<MemoryKv>, b: u8| {
+            let idx = hybrid.tree.slot_at(&NodeHash([b; 32]), 4).unwrap();
+            matches!(hybrid.tree.nodes[idx], Slot::Pruned { .. })
+        };
+
+        // Subtree 0 only holds a zero leaf, it stays in memory uncounted.
+        hybrid.insert(&NodeHash([0; 32]), [0; 32], 0).unwrap();
+        hybrid.insert(&NodeHash([1; 32]), [1; 32], 1).unwrap();
+        hybrid.insert(&NodeHash([2; 32]), [2; 32], 2).unwrap();
+        assert_eq!(hybrid.resident(), 2);
+        assert!(!pruned(&hybrid, 0));
+
+        // Reading subtree 1 makes subtree 2 the least recently used one.
+        hybrid.get(&NodeHash([1; 32])).unwrap();
+        hybrid.insert(&NodeHash([3; 32]), [3; 32], 3).unwrap();
+        assert!(pruned(&hybrid, 2));
+        assert!(!pruned(&hybrid, 1));
+        assert!(!pruned(&hybrid, 3));
+        assert_eq!(hybrid.resident(), 2);
+        assert_eq!(hybrid.lru.len(), 2);
+    }
+}
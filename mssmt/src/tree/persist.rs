@@ -0,0 +1,519 @@
+//! Content addressed persistence of the tree into a [`KvStore`].
+//!
+//! Every non empty node is stored under `n || hash`, empty subtrees are
+//! never stored since they are known from the empty tree. The latest
+//! persisted root is kept under [`ROOT_KEY`], older roots stay loadable as
+//! long as their nodes are not deleted.
+//!
+//! The tree tracks the slots changed since they were last written, so a
+//! persist only writes the path of each leaf inserted since the previous
+//! one. Loaded nodes start clean, they are already in the store they were
+//! loaded from.
+//!
+//! [`Tree::verify_store`] checks a store without loading the tree, reporting
+//! the node records the latest root doesn't reach along with the ones a load
+//! would reject.
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use anyhow::{bail, Context};
+
+use super::{LeafNode, Link, NodeHash, Slot, Tree, branch_hash, leaf_hash};
+use crate::store::KvStore;
+
+pub const ROOT_KEY: &[u8] = b"root";
+const NODE_PREFIX: u8 = b'n';
+
+const BRANCH_TAG: u8 = 0;
+const LEAF_TAG: u8 = 1;
+
+pub(super) const NODE_KEY_LEN: usize = 33;
+
+/// `tag || left || right || sum`
+pub(super) const BRANCH_RECORD_LEN: usize = 73;
+
+/// `tag || value || sum`
+pub(super) const LEAF_RECORD_LEN: usize = 41;
+
+fn node_key(hash: &NodeHash) -> [u8; NODE_KEY_LEN] {
+    let mut key = [NODE_PREFIX; NODE_KEY_LEN];
+    key[1..].copy_from_slice(hash.as_bytes());
+    key
+}
+
+/// Hash of the node a record holds, `None` if it isn't a node record.
+fn record_hash(record: &[u8]) -> Option<NodeHash> {
+    match (record.first(), record.len()) {
+        (Some(&BRANCH_TAG), BRANCH_RECORD_LEN) => {
+            let left = NodeHash::from_slice(&record[1..33]).ok()?;
+            let right = NodeHash::from_slice(&record[33..65]).ok()?;
+            Some(branch_hash(&left, &right, u64::from_be_bytes(record[65..73].try_into().ok()?)))
+        },
+        (Some(&LEAF_TAG), LEAF_RECORD_LEN) => {
+            Some(leaf_hash(&record[1..33].try_into().ok()?, u64::from_be_bytes(record[33..41].try_into().ok()?)))
+        },
+        _ => None,
+    }
+}
+
+/// Outcome of [`Tree::verify_store`].
+#[derive(Debug, Default)]
+pub struct StoreReport {
+    /// Number of node records reachable from the latest root.
+    pub reachable: usize,
+
+    /// Node records the latest root doesn't reach, including the ones only
+    /// older roots do.
+    pub unreachable: Vec<NodeHash>,
+
+    /// Node records [`Tree::load`] would reject, reachable or not.
+    pub invalid: Vec<NodeHash>,
+
+    /// Nodes of the latest root absent from the store.
+    pub missing: Vec<NodeHash>,
+}
+
+impl StoreReport {
+    pub fn is_ok(&self) -> bool {
+        self.unreachable.is_empty() && self.invalid.is_empty() && self.missing.is_empty()
+    }
+}
+
+impl Tree {
+    /// Writes the nodes changed since the last persist to `kv` and records
+    /// the root as the latest one. Unchanged nodes are expected to be in
+    /// `kv` already, from an earlier persist or load, use
+    /// [`Tree::persist_all`] to write the tree to another store.
+    pub fn persist<S: KvStore>(&mut self, kv: &mut S) -> anyhow::Result<NodeHash> {
+        let root = self.persist_link(self.root, 0, kv)?;
+        kv.put(ROOT_KEY, root.as_bytes())?;
+
+        Ok(root)
+    }
+
+    /// Writes every node of the tree to `kv` whatever was persisted before,
+    /// e.g. to copy a loaded tree to another store, and records the root as
+    /// the latest one.
+    pub fn persist_all<S: KvStore>(&mut self, kv: &mut S) -> anyhow::Result<NodeHash> {
+        self.dirty.extend(0..self.nodes.len());
+        self.persist(kv)
+    }
+
+    /// Walks the latest root persisted in `kv` and scans every node record,
+    /// reporting the records that root doesn't reach and the ones failing
+    /// the checks of [`Tree::load`].
+    pub fn verify_store<S: KvStore>(kv: &S) -> anyhow::Result<StoreReport> {
+        let mut report = StoreReport::default();
+        let mut visited = HashMap::new();
+
+        if let Some(root) = kv.get(ROOT_KEY)? {
+            let root = NodeHash::from_slice(&root).map_err(anyhow::Error::msg)?;
+            Tree::init().verify_record(kv, 0, &root, &mut visited, &mut report)?;
+        }
+        report.reachable = visited.len() - report.missing.len();
+
+        for (key, record) in kv.scan_prefix(&[NODE_PREFIX])? {
+            let hash = NodeHash::from_slice(&key[1..]).map_err(anyhow::Error::msg)?;
+            if visited.contains_key(&hash) {
+                continue;
+            }
+
+            report.unreachable.push(hash);
+            if record_hash(&record) != Some(hash) {
+                report.invalid.push(hash);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Verifies `kv` and deletes the node records the latest root doesn't
+    /// reach, older roots become unloadable. Invalid or missing nodes of the
+    /// latest root can't be rebuilt from the store and are left as is.
+    /// Returns the report of the verification done before repairing.
+    pub fn repair_store<S: KvStore>(kv: &mut S) -> anyhow::Result<StoreReport> {
+        let report = Self::verify_store(kv)?;
+
+        for hash in &report.unreachable {
+            kv.delete(&node_key(hash))?;
+        }
+
+        Ok(report)
+    }
+
+    /// Loads the latest root persisted in `kv`.
+    pub fn load<S: KvStore>(kv: &S) -> anyhow::Result<Tree> {
+        let root = kv.get(ROOT_KEY)?.context("No root persisted")?;
+        let root = NodeHash::from_slice(&root).map_err(anyhow::Error::msg)?;
+
+        Self::load_root(kv, &root)
+    }
+
+    /// Loads the tree with the given root, checking every node hash on the
+    /// way.
+    pub fn load_root<S: KvStore>(kv: &S, root: &NodeHash) -> anyhow::Result<Tree> {
+        let mut tree = Tree::init();
+        tree.root = tree.load_link(kv, 0, root, None)?;
+
+        Ok(tree)
+    }
+
+    /// Loads the tree with the given root down to `level`, subtrees rooted
+    /// at that level are left [`Slot::Pruned`] in the store.
+    pub(super) fn load_pruned<S: KvStore>(kv: &S, root: &NodeHash, level: usize) -> anyhow::Result<Tree> {
+        let mut tree = Tree::init();
+        tree.root = tree.load_link(kv, 0, root, Some(level))?;
+
+        Ok(tree)
+    }
+
+    pub(super) fn persist_link<S: KvStore>(&mut self, link: Link, level: usize, kv: &mut S) -> anyhow::Result<NodeHash> {
+        let hash = self.link_hash(link, level);
+
+        let Some(idx) = link.filter(|idx| self.dirty.contains(idx)) else {
+            return Ok(hash);
+        };
+
+        let mut value = Vec::with_capacity(BRANCH_RECORD_LEN);
+
+        match &self.nodes[idx] {
+            &Slot::Branch { left, right, sum, .. } => {
+                let left = self.persist_link(left, level + 1, kv)?;
+                let right = self.persist_link(right, level + 1, kv)?;
+
+                value.push(BRANCH_TAG);
+                value.extend_from_slice(left.as_bytes());
+                value.extend_from_slice(right.as_bytes());
+                value.extend_from_slice(&sum.to_be_bytes());
+            },
+
+            Slot::Leaf(ln) => {
+                value.push(LEAF_TAG);
+                value.extend_from_slice(&ln.value);
+                value.extend_from_slice(&ln.sum.to_be_bytes());
+            },
+
+            // Already in the store, that's where it was evicted to.
+            Slot::Pruned { .. } => return Ok(hash),
+        }
+
+        kv.put(&node_key(&hash), &value)?;
+        self.dirty.remove(&idx);
+
+        Ok(hash)
+    }
+
+    pub(super) fn load_link<S: KvStore>(&mut self, kv: &S, level: usize, hash: &NodeHash, prune_at: Option<usize>) -> anyhow::Result<Link> {
+        if self.tree[level].hash() == *hash {
+            return Ok(None);
+        }
+
+        let record = kv.get(&node_key(hash))?.with_context(|| format!("Missing node {} at level {}", hash, level))?;
+        let is_leaf_level = level == self.depth();
+
+        if prune_at == Some(level) {
+            let sum = match (record.first(), record.len()) {
+                (Some(&BRANCH_TAG), BRANCH_RECORD_LEN) if !is_leaf_level => &record[65..73],
+                (Some(&LEAF_TAG), LEAF_RECORD_LEN) if is_leaf_level => &record[33..41],
+                _ => bail!("Invalid node {} at level {}", hash, level),
+            };
+            let sum = u64::from_be_bytes(sum.try_into()?);
+
+            return Ok(Some(self.alloc_clean(Slot::Pruned { hash: *hash, sum })));
+        }
+
+        let (slot, computed) = match (record.first(), record.len()) {
+            (Some(&BRANCH_TAG), BRANCH_RECORD_LEN) if !is_leaf_level => {
+                let left = NodeHash::from_slice(&record[1..33]).map_err(anyhow::Error::msg)?;
+                let right = NodeHash::from_slice(&record[33..65]).map_err(anyhow::Error::msg)?;
+                let sum = u64::from_be_bytes(record[65..73].try_into()?);
+
+                let left = self.load_link(kv, level + 1, &left, prune_at)?;
+                let right = self.load_link(kv, level + 1, &right, prune_at)?;
+                if self.link_sum(left).checked_add(self.link_sum(right)) != Some(sum) {
+                    bail!("Branch {} sum doesn't match its children", hash);
+                }
+
+                let computed = branch_hash(&self.link_hash(left, level + 1), &self.link_hash(right, level + 1), sum);
+                (Slot::Branch { left, right, sum, hash: OnceLock::from(computed) }, computed)
+            },
+
+            (Some(&LEAF_TAG), LEAF_RECORD_LEN) if is_leaf_level => {
+                let value: [u8; 32] = record[1..33].try_into()?;
+                let sum = u64::from_be_bytes(record[33..41].try_into()?);
+
+                let computed = leaf_hash(&value, sum);
+                (Slot::Leaf(LeafNode { value, sum, hash: OnceLock::from(computed) }), computed)
+            },
+
+            _ => bail!("Invalid node {} at level {}", hash, level),
+        };
+
+        if computed != *hash {
+            bail!("Node stored under {} hashes to {}", hash, computed);
+        }
+
+        Ok(Some(self.alloc_clean(slot)))
+    }
+
+    /// Checks the record of the node at `level` and the ones below it the
+    /// way [`Tree::load_link`] does, returning its sum if it is valid.
+    fn verify_record<S: KvStore>(&self, kv: &S, level: usize, hash: &NodeHash, visited: &mut HashMap<NodeHash, Option<u64>>, report: &mut StoreReport) -> anyhow::Result<Option<u64>> {
+        if self.tree[level].hash() == *hash {
+            return Ok(Some(0));
+        }
+        if let Some(sum) = visited.get(hash) {
+            return Ok(*sum);
+        }
+
+        // Recorded before walking the children, a corrupted record could
+        // link back to itself.
+        visited.insert(*hash, None);
+        let Some(record) = kv.get(&node_key(hash))? else {
+            report.missing.push(*hash);
+            return Ok(None);
+        };
+
+        let is_leaf_level = level == self.depth();
+        let sum = match (record.first(), record.len()) {
+            (Some(&BRANCH_TAG), BRANCH_RECORD_LEN) if !is_leaf_level => {
+                let left = NodeHash::from_slice(&record[1..33]).map_err(anyhow::Error::msg)?;
+                let right = NodeHash::from_slice(&record[33..65]).map_err(anyhow::Error::msg)?;
+                let sum = u64::from_be_bytes(record[65..73].try_into()?);
+
+                let left = self.verify_record(kv, level + 1, &left, visited, report)?;
+                let right = self.verify_record(kv, level + 1, &right, visited, report)?;
+
+                // Invalid children are reported on their own.
+                match (left, right) {
+                    (Some(left), Some(right)) if left.checked_add(right) != Some(sum) => None,
+                    _ => Some(sum),
+                }
+            },
+            (Some(&LEAF_TAG), LEAF_RECORD_LEN) if is_leaf_level => Some(u64::from_be_bytes(record[33..41].try_into()?)),
+            _ => None,
+        };
+
+        let sum = sum.filter(|_| record_hash(&record) == Some(*hash));
+        if sum.is_none() {
+            report.invalid.push(*hash);
+        }
+        visited.insert(*hash, sum);
+
+        Ok(sum)
+    }
+
+    /// Allocates a slot already in the store.
+    pub(super) fn alloc_clean(&mut self, slot: Slot) -> usize {
+        let idx = self.alloc(slot);
+        self.dirty.remove(&idx);
+        idx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{store::{KvStore, MemoryKv}, tree::{NodeHash, Tree, leaf_hash}};
+
+    use super::{ROOT_KEY, node_key};
+
+    #[test]
+    fn persist_and_load() {
+        let mut kv = MemoryKv::default();
+
+        let mut ms_tree = Tree::init();
+        ms_tree.insert(&NodeHash([1; 32]), [1; 32], 10).unwrap();
+        ms_tree.insert(&NodeHash([2; 32]), [2; 32], 20).unwrap();
+        let first = ms_tree.persist(&mut kv).unwrap();
+
+        ms_tree.insert(&NodeHash([3; 32]), [3; 32], 30).unwrap();
+        let second = ms_tree.persist(&mut kv).unwrap();
+
+        let loaded = Tree::load(&kv).unwrap();
+        assert_eq!(loaded.root_hash(), second);
+        assert_eq!(loaded.root_sum(), 60);
+        assert!(loaded.verify_integrity().is_ok());
+
+        // Older roots stay loadable.
+        let old = Tree::load_root(&kv, &first).unwrap();
+        assert_eq!(old.root_hash(), first);
+        assert_eq!(old.root_sum(), 30);
+
+        // Empty trees are never stored.
+        let mut empty = Tree::init();
+        let mut empty_kv = MemoryKv::default();
+        empty.persist(&mut empty_kv).unwrap();
+        assert_eq!(empty_kv.len(), 1);
+        assert!(empty_kv.get(ROOT_KEY).unwrap().is_some());
+    }
+
+    #[test]
+    fn verify_and_repair_store() {
+        let mut kv = MemoryKv::default();
+        let mut ms_tree = Tree::init();
+        ms_tree.insert(&NodeHash([1; 32]), [1; 32], 10).unwrap();
+        ms_tree.insert(&NodeHash([2; 32]), [2; 32], 20).unwrap();
+        ms_tree.persist(&mut kv).unwrap();
+
+        let report = Tree::verify_store(&kv).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.reachable, kv.len() - 1);
+
+        // An orphan record, as left by an interrupted persist, and a
+        // corrupted leaf of the latest root.
+        let orphan = leaf_hash(&[3; 32], 30);
+        kv.put(&node_key(&orphan), &[&[1][..], &[3; 32], &30u64.to_be_bytes()].concat()).unwrap();
+        let leaf = leaf_hash(&[1; 32], 10);
+        let mut record = kv.get(&node_key(&leaf)).unwrap().unwrap();
+        record[1] ^= 1;
+        kv.put(&node_key(&leaf), &record).unwrap();
+
+        let report = Tree::verify_store(&kv).unwrap();
+        assert_eq!(report.unreachable, vec![orphan]);
+        assert_eq!(report.invalid, vec![leaf]);
+        assert!(report.missing.is_empty());
+
+        // Repairing drops the orphan, the corrupted leaf can only be
+        // restored from a copy of the tree.
+        let written = kv.len();
+        Tree::repair_store(&mut kv).unwrap();
+        assert_eq!(kv.len(), written - 1);
+        let report = Tree::verify_store(&kv).unwrap();
+        assert!(report.unreachable.is_empty());
+        assert_eq!(report.invalid, vec![leaf]);
+
+        ms_tree.persist_all(&mut kv).unwrap();
+        assert!(Tree::verify_store(&kv).unwrap().is_ok());
+
+        kv.delete(&node_key(&leaf)).unwrap();
+        assert_eq!(Tree::verify_store(&kv).unwrap().missing, vec![leaf]);
+    }
+
+    #[test]
+    fn load_detects_corruption() {
+        let mut kv = MemoryKv::default();
+
+        let mut ms_tree = Tree::init();
+        ms_tree.insert(&NodeHash([1; 32]), [1; 32], 10).unwrap();
+        ms_tree.persist(&mut kv).unwrap();
+
+        let leaf = leaf_hash(&[1; 32], 10);
+        let mut record = kv.get(&node_key(&leaf)).unwrap().unwrap();
+        record[1] ^= 1;
+        kv.put(&node_key(&leaf), &record).unwrap();
+        assert!(Tree::load(&kv).is_err());
+
+        kv.delete(&node_key(&leaf)).unwrap();
+        assert!(Tree::load(&kv).is_err());
+    }
+
+    /// Store failing every write after the first `writes_left`, as if the
+    /// process was killed.
+    struct CrashingKv<'a> {
+        inner: &'a mut MemoryKv,
+        writes_left: usize,
+    }
+
+    impl KvStore for CrashingKv<'_> {
+        fn get(&self, key: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+            self.inner.get(key)
+        }
+
+        fn put(&mut self, key: &[u8], value: &[u8]) -> anyhow::Result<()> {
+            if self.writes_left == 0 {
+                anyhow::bail!("Killed");
+            }
+            self.writes_left -= 1;
+            self.inner.put(key, value)
+        }
+
+        fn delete(&mut self, key: &[u8]) -> anyhow::Result<()> {
+            self.inner.delete(key)
+        }
+
+        fn scan_prefix(&self, prefix: &[u8]) -> anyhow::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+            self.inner.scan_prefix(prefix)
+        }
+    }
+
+    #[test]
+    fn persists_only_changed_nodes() {
+        let mut kv = MemoryKv::default();
+
+        let mut ms_tree = Tree::init();
+        ms_tree.insert(&NodeHash([1; 32]), [1; 32], 10).unwrap();
+        ms_tree.persist(&mut kv).unwrap();
+        let written = kv.len();
+
+        // Nothing changed, only the root is written again.
+        ms_tree.persist(&mut kv).unwrap();
+        assert_eq!(kv.len(), written);
+        assert!(ms_tree.dirty.is_empty());
+
+        // A new leaf only writes its own path, the first leaf's branches
+        // below their common prefix are left alone.
+        ms_tree.insert(&NodeHash([2; 32]), [2; 32], 20).unwrap();
+        assert_eq!(ms_tree.dirty.len(), ms_tree.depth() + 1);
+        let root = ms_tree.persist(&mut kv).unwrap();
+        assert!(ms_tree.dirty.is_empty());
+        assert_eq!(Tree::load(&kv).unwrap().root_hash(), root);
+
+        // Loaded trees start clean.
+        let mut loaded = Tree::load(&kv).unwrap();
+        assert!(loaded.dirty.is_empty());
+        loaded.insert(&NodeHash([3; 32]), [3; 32], 30).unwrap();
+        let root = loaded.persist(&mut kv).unwrap();
+        assert_eq!(Tree::load(&kv).unwrap().root_hash(), root);
+    }
+
+    #[test]
+    fn persist_all_copies_a_loaded_tree() {
+        let mut kv = MemoryKv::default();
+
+        let mut ms_tree = Tree::init();
+        ms_tree.insert(&NodeHash([1; 32]), [1; 32], 10).unwrap();
+        ms_tree.insert(&NodeHash([2; 32]), [2; 32], 20).unwrap();
+        let root = ms_tree.persist(&mut kv).unwrap();
+
+        // Loaded nodes are clean, only the root would reach another store.
+        let mut loaded = Tree::load(&kv).unwrap();
+        let mut copy = MemoryKv::default();
+        assert_eq!(loaded.persist_all(&mut copy).unwrap(), root);
+        assert_eq!(copy.len(), kv.len());
+
+        let reloaded = Tree::load(&copy).unwrap();
+        assert_eq!(reloaded.root_hash(), root);
+        assert_eq!(reloaded.root_sum(), 30);
+        assert!(reloaded.verify_integrity().is_ok());
+    }
+
+    #[test]
+    fn recovers_from_interrupted_persist() {
+        // The root is written last, so a persist killed after any number of
+        // writes leaves the previous root intact and loadable.
+        for writes in (0..).step_by(20) {
+            let mut kv = MemoryKv::default();
+            let mut ms_tree = Tree::init();
+            ms_tree.insert(&NodeHash([1; 32]), [1; 32], 10).unwrap();
+            let first = ms_tree.persist(&mut kv).unwrap();
+
+            ms_tree.insert(&NodeHash([2; 32]), [2; 32], 20).unwrap();
+            let result = ms_tree.persist(&mut CrashingKv { inner: &mut kv, writes_left: writes });
+            let loaded = Tree::load(&kv).unwrap();
+            assert!(loaded.verify_integrity().is_ok());
+
+            match result {
+                Ok(root) => {
+                    assert_eq!(loaded.root_hash(), root);
+                    break;
+                },
+                Err(_) => assert_eq!(loaded.root_hash(), first),
+            }
+
+            // The nodes written before the crash stay clean, persisting
+            // again writes the rest.
+            let root = ms_tree.persist(&mut kv).unwrap();
+            assert_eq!(Tree::load(&kv).unwrap().root_hash(), root);
+        }
+    }
+}
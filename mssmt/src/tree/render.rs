@@ -0,0 +1,121 @@
+//! Text dumps of a tree, handy to find where a root diverges from the one
+//! computed by another implementation.
+
+use std::fmt::Write;
+
+use super::{Link, NodeHash, Slot, Tree};
+
+/// Number of hex characters of the hashes kept when rendering.
+const HASH_PREFIX_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderFormat {
+    /// One node per line, indented by level. Default subtrees are collapsed
+    /// into a single line.
+    Ascii,
+
+    /// Graphviz `dot` graph, default subtrees are drawn dashed.
+    Dot,
+}
+
+fn short(hash: &NodeHash) -> String {
+    let mut s = hash.to_string();
+    s.truncate(HASH_PREFIX_LEN);
+    s
+}
+
+impl Tree {
+    pub fn render(&self, format: RenderFormat) -> String {
+        let mut out = String::new();
+
+        match format {
+            RenderFormat::Ascii => {
+                self.render_ascii(self.root, 0, "root", &mut out);
+            },
+
+            RenderFormat::Dot => {
+                out.push_str("digraph mssmt {\n    node [shape=box, fontname=monospace];\n");
+                self.render_dot(self.root, 0, &mut 0, &mut out);
+                out.push_str("}\n");
+            },
+        }
+
+        out
+    }
+
+    fn kind(&self, link: Link) -> &'static str {
+        match link.map(|idx| &self.nodes[idx]) {
+            None => "default",
+            Some(Slot::Branch { .. }) => "branch",
+            Some(Slot::Leaf(_)) => "leaf",
+            Some(Slot::Pruned { .. }) => "pruned",
+        }
+    }
+
+    fn render_ascii(&self, link: Link, level: usize, side: &str, out: &mut String) {
+        let _ = writeln!(
+            out,
+            "{:indent$}{} {} {} sum={}",
+            "", side, self.kind(link), short(&self.link_hash(link, level)), self.link_sum(link),
+            indent = level
+        );
+
+        if let Some((left, right)) = link.and_then(|idx| self.children(idx)) {
+            self.render_ascii(left, level + 1, "L", out);
+            self.render_ascii(right, level + 1, "R", out);
+        }
+    }
+
+    /// Renders `link` and its children, returns the id of the `dot` node.
+    fn render_dot(&self, link: Link, level: usize, next_id: &mut usize, out: &mut String) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+
+        let kind = self.kind(link);
+        let style = if link.is_none() { ", style=dashed" } else { "" };
+
+        let _ = writeln!(
+            out,
+            "    n{} [label=\"{} {}\\nsum={}\"{}];",
+            id, kind, short(&self.link_hash(link, level)), self.link_sum(link), style
+        );
+
+        if let Some((left, right)) = link.and_then(|idx| self.children(idx)) {
+            let left = self.render_dot(left, level + 1, next_id, out);
+            let right = self.render_dot(right, level + 1, next_id, out);
+
+            let _ = writeln!(out, "    n{} -> n{} [label=0];", id, left);
+            let _ = writeln!(out, "    n{} -> n{} [label=1];", id, right);
+        }
+
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tree::{NodeHash, Tree, render::RenderFormat};
+
+    #[test]
+    fn render_small_tree() {
+        let mut ms_tree = Tree::with_depth(2).unwrap();
+        ms_tree.insert(&NodeHash([1; 32]), [1; 32], 7).unwrap();
+
+        let ascii = ms_tree.render(RenderFormat::Ascii);
+        let lines: Vec<&str> = ascii.lines().collect();
+
+        // key bits are 1 then 0: root -> R -> L.
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("root branch "));
+        assert!(lines[0].ends_with(" sum=7"));
+        assert!(lines[1].starts_with(" L default "));
+        assert!(lines[2].starts_with(" R branch "));
+        assert!(lines[3].starts_with("  L leaf "));
+        assert!(lines[4].starts_with("  R default "));
+
+        let dot = ms_tree.render(RenderFormat::Dot);
+        assert!(dot.starts_with("digraph mssmt {"));
+        assert_eq!(dot.matches("style=dashed").count(), 2);
+        assert_eq!(dot.matches(" -> ").count(), 4);
+    }
+}
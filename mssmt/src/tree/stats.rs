@@ -0,0 +1,97 @@
+//! Shape and size statistics of a tree, for capacity planning.
+
+use super::{Link, Slot, Tree, persist};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeStats {
+    pub leaves: usize,
+    pub branches: usize,
+
+    /// Subtrees evicted to a store, see [`HybridTree`](super::HybridTree).
+    pub pruned: usize,
+
+    /// Allocated node slots, including the ones no longer reachable.
+    pub slots: usize,
+
+    /// Number of leaves by the level their path stops being shared with any
+    /// other leaf, i.e. their depth once single child branches are
+    /// compacted away.
+    pub depth_histogram: Vec<usize>,
+
+    /// Bytes taken by the nodes once persisted, keys included.
+    pub storage_bytes: usize,
+}
+
+impl TreeStats {
+    /// Mean of [`TreeStats::depth_histogram`], zero for an empty tree.
+    pub fn average_depth(&self) -> f64 {
+        if self.leaves == 0 {
+            return 0.0;
+        }
+
+        let total: usize = self.depth_histogram.iter().enumerate().map(|(depth, count)| depth * count).sum();
+        total as f64 / self.leaves as f64
+    }
+}
+
+impl Tree {
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats { slots: self.nodes.len(), depth_histogram: vec![0; self.depth() + 1], ..TreeStats::default() };
+        self.collect_stats(self.root, 0, 0, &mut stats);
+
+        stats
+    }
+
+    /// `shared_until` is the level of the last branch on the path having
+    /// both children.
+    fn collect_stats(&self, link: Link, level: usize, shared_until: usize, stats: &mut TreeStats) {
+        let Some(idx) = link else {
+            return;
+        };
+
+        match &self.nodes[idx] {
+            Slot::Branch { left, right, .. } => {
+                stats.branches += 1;
+                stats.storage_bytes += persist::NODE_KEY_LEN + persist::BRANCH_RECORD_LEN;
+
+                let shared_until = if left.is_some() && right.is_some() { level + 1 } else { shared_until };
+                self.collect_stats(*left, level + 1, shared_until, stats);
+                self.collect_stats(*right, level + 1, shared_until, stats);
+            },
+
+            Slot::Leaf(_) => {
+                stats.leaves += 1;
+                stats.storage_bytes += persist::NODE_KEY_LEN + persist::LEAF_RECORD_LEN;
+                stats.depth_histogram[shared_until] += 1;
+            },
+
+            Slot::Pruned { .. } => stats.pruned += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tree::{NodeHash, Tree};
+
+    #[test]
+    fn stats() {
+        let mut ms_tree = Tree::with_depth(8).unwrap();
+        assert_eq!(ms_tree.stats().average_depth(), 0.0);
+
+        ms_tree.insert(&NodeHash([0; 32]), [1; 32], 1).unwrap();
+        let stats = ms_tree.stats();
+        assert_eq!((stats.leaves, stats.branches), (1, 8));
+        assert_eq!(stats.depth_histogram[0], 1);
+
+        // Splits at the root, then the left side at level 1.
+        ms_tree.insert(&NodeHash([1; 32]), [1; 32], 1).unwrap();
+        ms_tree.insert(&NodeHash([2; 32]), [1; 32], 1).unwrap();
+        let stats = ms_tree.stats();
+        assert_eq!((stats.leaves, stats.branches, stats.pruned), (3, 8 + 7 + 6, 0));
+        assert_eq!(stats.depth_histogram[1], 1);
+        assert_eq!(stats.depth_histogram[2], 2);
+        assert_eq!(stats.average_depth(), 5.0 / 3.0);
+        assert_eq!(stats.storage_bytes, 21 * (33 + 73) + 3 * (33 + 41));
+    }
+}
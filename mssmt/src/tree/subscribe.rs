@@ -0,0 +1,71 @@
+use std::sync::mpsc::{Receiver, channel};
+
+use super::{NodeHash, Tree};
+
+/// Root of the tree after a change, see [`Tree::subscribe_roots`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RootUpdate {
+    pub root: NodeHash,
+    pub sum: u64,
+
+    /// Incremented on every change of the tree.
+    pub version: u64,
+}
+
+impl Tree {
+    /// Returns a receiver getting the new root after every insert, or repair,
+    /// of the tree. Subscribers are dropped once their receiver is.
+    pub fn subscribe_roots(&mut self) -> Receiver<RootUpdate> {
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+
+        rx
+    }
+
+    /// Number of changes made to the tree since it was created.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub(super) fn publish_root(&mut self) {
+        self.version += 1;
+
+        // Avoid hashing the root on every change when nobody listens.
+        if self.subscribers.is_empty() {
+            return;
+        }
+
+        let update = RootUpdate { root: self.root_hash(), sum: self.root_sum(), version: self.version };
+        self.subscribers.retain(|tx| tx.send(update).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tree::{NodeHash, Tree};
+
+    #[test]
+    fn subscribe_roots() {
+        let mut ms_tree = Tree::init();
+        let rx = ms_tree.subscribe_roots();
+
+        ms_tree.insert(&NodeHash::new([1; 32]), [1; 32], 10).unwrap();
+        ms_tree.insert(&NodeHash::new([2; 32]), [2; 32], 20).unwrap();
+
+        // Failed inserts don't publish anything.
+        assert!(ms_tree.insert(&NodeHash::new([3; 32]), [3; 32], u64::MAX).is_err());
+
+        let updates: Vec<_> = rx.try_iter().collect();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].version, 1);
+        assert_eq!(updates[0].sum, 10);
+        assert_eq!(updates[1].version, 2);
+        assert_eq!(updates[1].sum, 30);
+        assert_eq!(updates[1].root, ms_tree.root_hash());
+
+        drop(rx);
+        ms_tree.insert(&NodeHash::new([4; 32]), [4; 32], 1).unwrap();
+        assert!(ms_tree.subscribers.is_empty());
+        assert_eq!(ms_tree.version(), 3);
+    }
+}
@@ -0,0 +1,154 @@
+//! Type-length-value streams as used by the asset, proof and address
+//! encodings.
+//!
+//! A stream is a sequence of records `type || length || value`, type and
+//! length being BigSize integers. Types must be strictly increasing, so
+//! every stream has a single canonical encoding.
+
+use anyhow::{bail, Context};
+
+pub mod value;
+pub mod varint;
+
+pub use value::Value;
+
+/// Encoded record of a stream, the value is left for the caller to decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record<'a> {
+    pub tlv_type: u64,
+    pub value: &'a [u8],
+}
+
+impl Record<'_> {
+    pub fn decode<T: Value>(&self) -> anyhow::Result<T> {
+        T::decode(self.value).with_context(|| format!("Invalid value for TLV type {}", self.tlv_type))
+    }
+
+    /// Even types must be understood by the reader, odd ones can be skipped
+    /// ("it's ok to be odd").
+    pub fn is_required(&self) -> bool {
+        self.tlv_type.is_multiple_of(2)
+    }
+}
+
+#[derive(Default)]
+pub struct Writer {
+    buf: Vec<u8>,
+    last_type: Option<u64>,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a record with an already encoded value.
+    pub fn record(&mut self, tlv_type: u64, value: &[u8]) -> anyhow::Result<&mut Self> {
+        if self.last_type.is_some_and(|last| last >= tlv_type) {
+            bail!("TLV type {} written after type {}", tlv_type, self.last_type.unwrap_or_default());
+        }
+        self.last_type = Some(tlv_type);
+
+        varint::write_big_size(tlv_type, &mut self.buf);
+        varint::write_big_size(value.len() as u64, &mut self.buf);
+        self.buf.extend_from_slice(value);
+
+        Ok(self)
+    }
+
+    pub fn put<T: Value>(&mut self, tlv_type: u64, value: &T) -> anyhow::Result<&mut Self> {
+        let mut encoded = Vec::new();
+        value.encode(&mut encoded);
+
+        self.record(tlv_type, &encoded)
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Reads the records of a stream one by one, rejecting non canonical
+/// streams.
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    last_type: Option<u64>,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, last_type: None }
+    }
+
+    /// Returns the next record, or `None` once the stream is consumed.
+    pub fn next_record(&mut self) -> anyhow::Result<Option<Record<'a>>> {
+        if self.bytes.is_empty() {
+            return Ok(None);
+        }
+
+        let tlv_type = varint::read_big_size(&mut self.bytes).context("Invalid TLV type")?;
+        if let Some(last) = self.last_type
+            && last >= tlv_type
+        {
+            bail!("TLV type {} read after type {}", tlv_type, last);
+        }
+        self.last_type = Some(tlv_type);
+
+        let len = varint::read_big_size_len(&mut self.bytes).context("Invalid TLV length")?;
+        if len > self.bytes.len() {
+            bail!("TLV type {} has length {} but only {} bytes are left", tlv_type, len, self.bytes.len());
+        }
+
+        let (value, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(Some(Record { tlv_type, value }))
+    }
+
+    /// Reads all the remaining records.
+    pub fn records(mut self) -> anyhow::Result<Vec<Record<'a>>> {
+        let mut records = Vec::new();
+        while let Some(record) = self.next_record()? {
+            records.push(record);
+        }
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Reader, Writer};
+
+    #[test]
+    fn stream_roundtrip() {
+        let mut writer = Writer::new();
+        writer.put(0, &1u8).unwrap().put(2, &[7u8; 32]).unwrap().put(253, &u64::MAX).unwrap().record(70000, &[]).unwrap();
+        assert!(writer.put(70000, &1u8).is_err());
+        let stream = writer.finish();
+
+        let records = Reader::new(&stream).records().unwrap();
+        assert_eq!(records.iter().map(|r| r.tlv_type).collect::<Vec<_>>(), vec![0, 2, 253, 70000]);
+        assert_eq!(records[0].decode::<u8>().unwrap(), 1);
+        assert_eq!(records[1].decode::<[u8; 32]>().unwrap(), [7; 32]);
+        assert_eq!(records[2].decode::<u64>().unwrap(), u64::MAX);
+        assert!(records[2].decode::<u32>().is_err());
+        assert!(records[3].value.is_empty());
+        assert!(records[0].is_required() && !records[2].is_required());
+    }
+
+    #[test]
+    fn rejects_non_canonical_streams() {
+        // Types out of order or repeated.
+        assert!(Reader::new(&[2, 0, 1, 0]).records().is_err());
+        assert!(Reader::new(&[1, 0, 1, 0]).records().is_err());
+
+        // Value longer than the stream.
+        assert!(Reader::new(&[1, 2, 0]).records().is_err());
+
+        // Truncated type.
+        assert!(Reader::new(&[0xfd, 0]).records().is_err());
+
+        assert!(Reader::new(&[]).records().unwrap().is_empty());
+    }
+}
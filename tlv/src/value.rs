@@ -0,0 +1,56 @@
+//! Encoding of the record values themselves.
+
+use anyhow::bail;
+
+/// Type that can be stored as the value of a record. Integers are big endian
+/// and fixed size, byte arrays are stored as is.
+pub trait Value: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Decodes the whole of `bytes`, trailing bytes are an error.
+    fn decode(bytes: &[u8]) -> anyhow::Result<Self>;
+}
+
+macro_rules! int_value {
+    ($($int:ty),*) => {
+        $(
+            impl Value for $int {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+                    match bytes.try_into() {
+                        Ok(bytes) => Ok(<$int>::from_be_bytes(bytes)),
+                        Err(_) => bail!("Expected {} bytes, got {}", size_of::<$int>(), bytes.len()),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+int_value!(u8, u16, u32, u64);
+
+impl<const N: usize> Value for [u8; N] {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+
+    fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        match bytes.try_into() {
+            Ok(bytes) => Ok(bytes),
+            Err(_) => bail!("Expected {} bytes, got {}", N, bytes.len()),
+        }
+    }
+}
+
+impl Value for Vec<u8> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+
+    fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(bytes.to_vec())
+    }
+}
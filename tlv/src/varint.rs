@@ -0,0 +1,183 @@
+//! Variable length integers: BigSize, used by TLV streams, and Bitcoin's
+//! CompactSize, used by the transaction encodings embedded in proofs. Both
+//! share the same prefixes, BigSize being big endian and CompactSize little
+//! endian.
+
+use std::fmt::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarIntError {
+    /// The encoding needs more bytes than are left.
+    Truncated { needed: usize, available: usize },
+
+    /// The value has a shorter encoding.
+    NonCanonical(u64),
+
+    /// The value doesn't fit the integer type it's read into.
+    Overflow(u64),
+}
+
+impl Display for VarIntError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Truncated { needed, available } => write!(f, "Truncated varint, needed {} bytes but {} are left", needed, available),
+            Self::NonCanonical(n) => write!(f, "Non canonical varint {}", n),
+            Self::Overflow(n) => write!(f, "Varint {} overflows its type", n),
+        }
+    }
+}
+
+impl std::error::Error for VarIntError {}
+
+/// Number of bytes `n` is encoded with, the same for both encodings.
+pub fn encoded_len(n: u64) -> usize {
+    match n {
+        0..0xfd => 1,
+        0xfd..=0xffff => 3,
+        0x10000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+fn write(n: u64, big_endian: bool, out: &mut Vec<u8>) {
+    let (prefix, width) = match encoded_len(n) {
+        1 => return out.push(n as u8),
+        3 => (0xfd, 2),
+        5 => (0xfe, 4),
+        _ => (0xff, 8),
+    };
+
+    out.push(prefix);
+    if big_endian {
+        out.extend_from_slice(&n.to_be_bytes()[8 - width..]);
+    } else {
+        out.extend_from_slice(&n.to_le_bytes()[..width]);
+    }
+}
+
+fn read(bytes: &mut &[u8], big_endian: bool) -> Result<u64, VarIntError> {
+    let Some((&prefix, rest)) = bytes.split_first() else {
+        return Err(VarIntError::Truncated { needed: 1, available: 0 });
+    };
+
+    let width = match prefix {
+        0xfd => 2,
+        0xfe => 4,
+        0xff => 8,
+        _ => {
+            *bytes = rest;
+            return Ok(prefix as u64);
+        },
+    };
+
+    if rest.len() < width {
+        return Err(VarIntError::Truncated { needed: width + 1, available: bytes.len() });
+    }
+
+    let (int, rest) = rest.split_at(width);
+    let mut buf = [0; 8];
+    let n = if big_endian {
+        buf[8 - width..].copy_from_slice(int);
+        u64::from_be_bytes(buf)
+    } else {
+        buf[..width].copy_from_slice(int);
+        u64::from_le_bytes(buf)
+    };
+
+    if encoded_len(n) != width + 1 {
+        return Err(VarIntError::NonCanonical(n));
+    }
+
+    *bytes = rest;
+    Ok(n)
+}
+
+pub fn write_big_size(n: u64, out: &mut Vec<u8>) {
+    write(n, true, out)
+}
+
+/// Reads a BigSize from the front of `bytes`, advancing it only on success.
+pub fn read_big_size(bytes: &mut &[u8]) -> Result<u64, VarIntError> {
+    read(bytes, true)
+}
+
+pub fn write_compact_size(n: u64, out: &mut Vec<u8>) {
+    write(n, false, out)
+}
+
+/// Reads a CompactSize from the front of `bytes`, advancing it only on
+/// success.
+pub fn read_compact_size(bytes: &mut &[u8]) -> Result<u64, VarIntError> {
+    read(bytes, false)
+}
+
+/// Reads a BigSize length, which must fit in a usize.
+pub fn read_big_size_len(bytes: &mut &[u8]) -> Result<usize, VarIntError> {
+    let n = read_big_size(bytes)?;
+    usize::try_from(n).map_err(|_| VarIntError::Overflow(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VarIntError, encoded_len, read_big_size, read_compact_size, write_big_size, write_compact_size};
+
+    const BOUNDARIES: [u64; 8] = [0, 0xfc, 0xfd, 0xffff, 0x10000, 0xffff_ffff, 0x1_0000_0000, u64::MAX];
+
+    #[test]
+    fn bolt_vectors() {
+        let vectors: [(u64, &[u8]); 5] = [
+            (0, &[0x00]),
+            (252, &[0xfc]),
+            (253, &[0xfd, 0x00, 0xfd]),
+            (65536, &[0xfe, 0x00, 0x01, 0x00, 0x00]),
+            (u64::MAX, &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+        ];
+
+        for (n, encoded) in vectors {
+            let mut out = Vec::new();
+            write_big_size(n, &mut out);
+            assert_eq!(out, encoded);
+        }
+
+        let mut out = Vec::new();
+        write_compact_size(0x1234, &mut out);
+        assert_eq!(out, [0xfd, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn boundaries() {
+        type Codec = (fn(u64, &mut Vec<u8>), fn(&mut &[u8]) -> Result<u64, VarIntError>, bool);
+        let codecs: [Codec; 2] = [(write_big_size, read_big_size, true), (write_compact_size, read_compact_size, false)];
+
+        for (write, read, big_endian) in codecs {
+            for n in BOUNDARIES {
+                let mut out = Vec::new();
+                write(n, &mut out);
+                assert_eq!(out.len(), encoded_len(n));
+
+                let mut bytes = &out[..];
+                assert_eq!(read(&mut bytes), Ok(n));
+                assert!(bytes.is_empty());
+
+                // Every truncation fails without consuming anything.
+                for len in 0..out.len() {
+                    let mut bytes = &out[..len];
+                    assert!(matches!(read(&mut bytes), Err(VarIntError::Truncated { .. })));
+                    assert_eq!(bytes.len(), len);
+                }
+            }
+
+            // Values one below each prefix range, encoded with that prefix.
+            for (prefix, width, n) in [(0xfd, 2, 0xfc), (0xfe, 4, 0xffff), (0xff, 8, 0xffff_ffff)] {
+                let mut padded = vec![prefix];
+                if big_endian {
+                    padded.extend_from_slice(&u64::to_be_bytes(n)[8 - width..]);
+                } else {
+                    padded.extend_from_slice(&u64::to_le_bytes(n)[..width]);
+                }
+
+                assert_eq!(read(&mut &padded[..]), Err(VarIntError::NonCanonical(n)));
+            }
+        }
+    }
+}
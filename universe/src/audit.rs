@@ -0,0 +1,198 @@
+//! Append-only audit log of state changes.
+//!
+//! Entries are stored under `a || seq` and chained: each one commits to the
+//! hash of the previous one, so editing or dropping an entry breaks every
+//! hash after it. The head of the chain is `a || "head"`, which never
+//! collides with an 8 byte sequence number.
+
+use anyhow::{bail, Context};
+use bitcoin::hashes::{Hash, sha256};
+use mssmt::{store::KvStore, tree::NodeHash};
+use tlv::{Reader, Writer};
+
+const AUDIT_PREFIX: u8 = b'a';
+const HEAD_KEY: &[u8] = b"ahead";
+
+const SEQ_TYPE: u64 = 0;
+const TIMESTAMP_TYPE: u64 = 2;
+const ACTOR_TYPE: u64 = 4;
+const ACTION_TYPE: u64 = 6;
+const DETAIL_TYPE: u64 = 8;
+const PREV_TYPE: u64 = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub seq: u64,
+
+    /// Unix time in seconds.
+    pub timestamp: u64,
+    pub actor: String,
+
+    /// What was changed, e.g. `mint`, `transfer`, `universe_insert` or
+    /// `federation_add`.
+    pub action: String,
+    pub detail: String,
+
+    /// Hash of the previous entry, zero for the first one.
+    pub prev: NodeHash,
+}
+
+impl AuditEntry {
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer
+            .put(SEQ_TYPE, &self.seq)
+            .and_then(|w| w.put(TIMESTAMP_TYPE, &self.timestamp))
+            .and_then(|w| w.record(ACTOR_TYPE, self.actor.as_bytes()))
+            .and_then(|w| w.record(ACTION_TYPE, self.action.as_bytes()))
+            .and_then(|w| w.record(DETAIL_TYPE, self.detail.as_bytes()))
+            .and_then(|w| w.put(PREV_TYPE, self.prev.as_bytes()))
+            .expect("types are written in order");
+
+        writer.finish()
+    }
+
+    fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        let records = Reader::new(bytes).records()?;
+        if records.iter().map(|r| r.tlv_type).ne([SEQ_TYPE, TIMESTAMP_TYPE, ACTOR_TYPE, ACTION_TYPE, DETAIL_TYPE, PREV_TYPE]) {
+            bail!("Invalid audit entry records");
+        }
+
+        Ok(AuditEntry {
+            seq: records[0].decode()?,
+            timestamp: records[1].decode()?,
+            actor: String::from_utf8(records[2].value.to_vec())?,
+            action: String::from_utf8(records[3].value.to_vec())?,
+            detail: String::from_utf8(records[4].value.to_vec())?,
+            prev: NodeHash::from(records[5].decode::<[u8; 32]>()?),
+        })
+    }
+
+    pub fn hash(&self) -> NodeHash {
+        sha256::Hash::hash(&self.encode()).into()
+    }
+}
+
+fn entry_key(seq: u64) -> [u8; 9] {
+    let mut key = [AUDIT_PREFIX; 9];
+    key[1..].copy_from_slice(&seq.to_be_bytes());
+    key
+}
+
+pub struct AuditLog<S: KvStore> {
+    kv: S,
+    next_seq: u64,
+    head: NodeHash,
+}
+
+impl<S: KvStore> AuditLog<S> {
+    /// Opens the log stored in `kv`, checking that its head is the last
+    /// entry.
+    pub fn open(kv: S) -> anyhow::Result<Self> {
+        let Some(head) = kv.get(HEAD_KEY)? else {
+            return Ok(AuditLog { kv, next_seq: 0, head: NodeHash::default() });
+        };
+
+        let seq = u64::from_be_bytes(head.get(..8).context("Invalid audit log head")?.try_into()?);
+        let head = NodeHash::from_slice(&head[8..]).map_err(anyhow::Error::msg)?;
+
+        let last = kv.get(&entry_key(seq))?.context("Missing last audit entry")?;
+        if AuditEntry::decode(&last)?.hash() != head {
+            bail!("Audit log head doesn't match its last entry");
+        }
+
+        Ok(AuditLog { kv, next_seq: seq + 1, head })
+    }
+
+    pub fn append(&mut self, timestamp: u64, actor: &str, action: &str, detail: &str) -> anyhow::Result<AuditEntry> {
+        let entry = AuditEntry {
+            seq: self.next_seq,
+            timestamp,
+            actor: actor.to_string(),
+            action: action.to_string(),
+            detail: detail.to_string(),
+            prev: self.head,
+        };
+        let hash = entry.hash();
+
+        self.kv.put(&entry_key(entry.seq), &entry.encode())?;
+
+        let mut head = entry.seq.to_be_bytes().to_vec();
+        head.extend_from_slice(hash.as_bytes());
+        self.kv.put(HEAD_KEY, &head)?;
+
+        self.next_seq += 1;
+        self.head = hash;
+
+        Ok(entry)
+    }
+
+    /// Hash of the last entry, to be published or anchored so the log can't
+    /// be rewritten unnoticed.
+    pub fn head(&self) -> NodeHash {
+        self.head
+    }
+
+    /// Entries from `seq` on, oldest first.
+    pub fn entries(&self, seq: u64) -> anyhow::Result<Vec<AuditEntry>> {
+        (seq..self.next_seq)
+            .map(|seq| AuditEntry::decode(&self.kv.get(&entry_key(seq))?.with_context(|| format!("Missing audit entry {}", seq))?))
+            .collect()
+    }
+
+    pub fn entries_with_action(&self, action: &str) -> anyhow::Result<Vec<AuditEntry>> {
+        Ok(self.entries(0)?.into_iter().filter(|entry| entry.action == action).collect())
+    }
+
+    /// Checks the whole chain, returning the sequence number of the first
+    /// entry not matching its predecessor.
+    pub fn verify(&self) -> anyhow::Result<Option<u64>> {
+        let mut prev = NodeHash::default();
+
+        for entry in self.entries(0)? {
+            if entry.prev != prev {
+                return Ok(Some(entry.seq));
+            }
+            prev = entry.hash();
+        }
+
+        Ok((prev != self.head).then_some(self.next_seq.saturating_sub(1)))
+    }
+
+    pub fn into_inner(self) -> S {
+        self.kv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mssmt::store::{KvStore, MemoryKv};
+
+    use super::{AuditEntry, AuditLog, entry_key};
+
+    #[test]
+    fn hash_chain() {
+        let mut log = AuditLog::open(MemoryKv::default()).unwrap();
+        log.append(1, "alice", "mint", "asset 01..").unwrap();
+        log.append(2, "sync", "universe_insert", "leaf 02..").unwrap();
+        log.append(3, "alice", "mint", "asset 03..").unwrap();
+
+        assert_eq!(log.entries(1).unwrap().len(), 2);
+        assert_eq!(log.entries_with_action("mint").unwrap().iter().map(|e| e.seq).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(log.verify().unwrap(), None);
+
+        let head = log.head();
+        let mut log = AuditLog::open(log.into_inner()).unwrap();
+        assert_eq!(log.head(), head);
+        let entry = log.append(4, "bob", "federation_add", "universe.example.com").unwrap();
+        assert_eq!(entry.seq, 3);
+        assert_eq!(entry.prev, head);
+
+        // Rewriting an entry breaks the chain after it.
+        let mut kv = log.into_inner();
+        let mut edited = AuditEntry::decode(&kv.get(&entry_key(1)).unwrap().unwrap()).unwrap();
+        edited.actor = "mallory".to_string();
+        kv.put(&entry_key(1), &edited.encode()).unwrap();
+        assert_eq!(AuditLog::open(kv).unwrap().verify().unwrap(), Some(2));
+    }
+}
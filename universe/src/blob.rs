@@ -0,0 +1,212 @@
+//! Content addressed store of proof files and asset metadata.
+//!
+//! Blobs are stored once under `b || sha256` however many leaves reference
+//! them, with their reference count under `c || sha256`. A blob is deleted
+//! when its last reference is released.
+//!
+//! Stored blobs start with the tag of their [`Compression`], blobs are
+//! hashed before compression so their hash doesn't depend on it.
+
+use anyhow::{bail, Context};
+use bitcoin::hashes::{Hash, sha256};
+use mssmt::store::KvStore;
+
+const BLOB_PREFIX: u8 = b'b';
+const REFCOUNT_PREFIX: u8 = b'c';
+
+const RAW_TAG: u8 = 0;
+const ZSTD_TAG: u8 = 1;
+
+/// Compression of the blobs written, blobs are read whatever they were
+/// written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+
+    /// zstd at the given level, blobs it doesn't shrink are kept raw.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+impl Compression {
+    fn encode(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        #[cfg(feature = "zstd")]
+        if let Compression::Zstd(level) = self {
+            let compressed = zstd::encode_all(data, *level)?;
+            if compressed.len() < data.len() {
+                return Ok([&[ZSTD_TAG][..], &compressed].concat());
+            }
+        }
+
+        Ok([&[RAW_TAG][..], data].concat())
+    }
+
+    fn decode(stored: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match stored.split_first() {
+            Some((&RAW_TAG, data)) => Ok(data.to_vec()),
+            #[cfg(feature = "zstd")]
+            Some((&ZSTD_TAG, data)) => Ok(zstd::decode_all(data)?),
+            #[cfg(not(feature = "zstd"))]
+            Some((&ZSTD_TAG, _)) => bail!("Blob compressed with zstd, built without the zstd feature"),
+            Some((tag, _)) => bail!("Unknown blob compression {}", tag),
+            None => bail!("Empty blob record"),
+        }
+    }
+}
+
+fn blob_key(prefix: u8, hash: &sha256::Hash) -> [u8; 33] {
+    let mut key = [prefix; 33];
+    key[1..].copy_from_slice(hash.as_byte_array());
+    key
+}
+
+pub struct BlobStore<S: KvStore> {
+    kv: S,
+    compression: Compression,
+}
+
+impl<S: KvStore> BlobStore<S> {
+    pub fn new(kv: S) -> Self {
+        Self::with_compression(kv, Compression::None)
+    }
+
+    pub fn with_compression(kv: S, compression: Compression) -> Self {
+        BlobStore { kv, compression }
+    }
+
+    /// Stores `data` if it isn't already and adds a reference to it.
+    pub fn put(&mut self, data: &[u8]) -> anyhow::Result<sha256::Hash> {
+        let hash = sha256::Hash::hash(data);
+
+        let refcount = self.refcount(&hash)?;
+        if refcount == 0 {
+            self.kv.put(&blob_key(BLOB_PREFIX, &hash), &self.compression.encode(data)?)?;
+        }
+        self.set_refcount(&hash, refcount + 1)?;
+
+        Ok(hash)
+    }
+
+    /// Returns the blob of `hash`, checking it wasn't altered.
+    pub fn get(&self, hash: &sha256::Hash) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some(stored) = self.kv.get(&blob_key(BLOB_PREFIX, hash))? else {
+            return Ok(None);
+        };
+        let data = Compression::decode(&stored)?;
+        if sha256::Hash::hash(&data) != *hash {
+            bail!("Blob {} is corrupted", hash);
+        }
+
+        Ok(Some(data))
+    }
+
+    /// Adds a reference to a stored blob, returning its new count.
+    pub fn retain(&mut self, hash: &sha256::Hash) -> anyhow::Result<u64> {
+        let refcount = self.refcount(hash)?;
+        if refcount == 0 {
+            bail!("Unknown blob {}", hash);
+        }
+
+        self.set_refcount(hash, refcount + 1)?;
+        Ok(refcount + 1)
+    }
+
+    /// Drops a reference to a blob, deleting it with its last reference.
+    /// Returns the references left.
+    pub fn release(&mut self, hash: &sha256::Hash) -> anyhow::Result<u64> {
+        let refcount = self.refcount(hash)?;
+        if refcount == 0 {
+            bail!("Unknown blob {}", hash);
+        }
+
+        if refcount == 1 {
+            self.kv.delete(&blob_key(BLOB_PREFIX, hash))?;
+            self.kv.delete(&blob_key(REFCOUNT_PREFIX, hash))?;
+        } else {
+            self.set_refcount(hash, refcount - 1)?;
+        }
+
+        Ok(refcount - 1)
+    }
+
+    pub fn refcount(&self, hash: &sha256::Hash) -> anyhow::Result<u64> {
+        match self.kv.get(&blob_key(REFCOUNT_PREFIX, hash))? {
+            Some(count) => Ok(u64::from_be_bytes(count.as_slice().try_into().context("Invalid reference count")?)),
+            None => Ok(0),
+        }
+    }
+
+    fn set_refcount(&mut self, hash: &sha256::Hash, refcount: u64) -> anyhow::Result<()> {
+        self.kv.put(&blob_key(REFCOUNT_PREFIX, hash), &refcount.to_be_bytes())
+    }
+
+    pub fn into_inner(self) -> S {
+        self.kv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::hashes::{Hash, sha256};
+    use mssmt::store::{KvStore, MemoryKv};
+
+    use super::{BLOB_PREFIX, BlobStore, RAW_TAG, blob_key};
+
+    #[test]
+    fn deduplicate_and_release() {
+        let mut blobs = BlobStore::new(MemoryKv::default());
+        let proof = vec![7; 4096];
+
+        let hash = blobs.put(&proof).unwrap();
+        assert_eq!(blobs.put(&proof).unwrap(), hash);
+        assert_eq!(blobs.retain(&hash).unwrap(), 3);
+        assert_eq!(blobs.get(&hash).unwrap(), Some(proof.clone()));
+
+        // One copy and its count, whatever the number of references.
+        assert_eq!(blobs.kv.len(), 2);
+
+        let unknown = sha256::Hash::hash(b"unknown");
+        assert!(blobs.retain(&unknown).is_err());
+        assert!(blobs.release(&unknown).is_err());
+
+        assert_eq!(blobs.release(&hash).unwrap(), 2);
+        assert_eq!(blobs.release(&hash).unwrap(), 1);
+        assert!(blobs.get(&hash).unwrap().is_some());
+        assert_eq!(blobs.release(&hash).unwrap(), 0);
+        assert_eq!(blobs.get(&hash).unwrap(), None);
+        assert!(blobs.kv.is_empty());
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let mut blobs = BlobStore::new(MemoryKv::default());
+        let hash = blobs.put(b"metadata").unwrap();
+
+        blobs.kv.put(&blob_key(BLOB_PREFIX, &hash), &[&[RAW_TAG][..], b"metadatA"].concat()).unwrap();
+        assert!(blobs.get(&hash).is_err());
+        blobs.kv.put(&blob_key(BLOB_PREFIX, &hash), &[9]).unwrap();
+        assert!(blobs.get(&hash).is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_compression() {
+        use super::Compression;
+
+        let mut blobs = BlobStore::with_compression(MemoryKv::default(), Compression::Zstd(3));
+        let proof: Vec<u8> = (0..4096u32).flat_map(|i| (i % 64).to_be_bytes()).collect();
+        let short = b"tiny";
+
+        let hash = blobs.put(&proof).unwrap();
+        let short_hash = blobs.put(short).unwrap();
+        assert_eq!(hash, sha256::Hash::hash(&proof));
+        assert!(blobs.kv.get(&blob_key(BLOB_PREFIX, &hash)).unwrap().unwrap().len() < proof.len() / 4);
+        assert_eq!(blobs.kv.get(&blob_key(BLOB_PREFIX, &short_hash)).unwrap().unwrap()[0], RAW_TAG);
+
+        // Readable whatever the compression the store is opened with.
+        let blobs = BlobStore::new(blobs.into_inner());
+        assert_eq!(blobs.get(&hash).unwrap(), Some(proof));
+        assert_eq!(blobs.get(&short_hash).unwrap().as_deref(), Some(&short[..]));
+    }
+}
@@ -0,0 +1,241 @@
+//! Cache of materialized proofs for the most requested leaves, for read
+//! heavy workloads like explorers.
+//!
+//! Every cached proof is tagged with the universe root it was built
+//! against, and a universe changing root drops its proofs. Universes can be
+//! pinned: their proofs don't count against the capacity and are never
+//! evicted, the least recently used proofs of the others are.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+};
+
+use mssmt::tree::NodeHash;
+
+use crate::id::UniverseId;
+
+struct Entry {
+    proof: Arc<[u8]>,
+    tick: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+pub struct ProofCache {
+    capacity: usize,
+    entries: HashMap<UniverseId, HashMap<NodeHash, Entry>>,
+
+    /// Proofs of the universes which aren't pinned by last access tick,
+    /// least recently used first.
+    lru: BTreeMap<u64, (UniverseId, NodeHash)>,
+
+    /// Root the cached proofs of each universe were built against.
+    roots: HashMap<UniverseId, NodeHash>,
+    pinned: HashSet<UniverseId>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl ProofCache {
+    /// Keeps at most `capacity` proofs of universes which aren't pinned.
+    pub fn new(capacity: usize) -> Self {
+        ProofCache {
+            capacity,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            roots: HashMap::new(),
+            pinned: HashSet::new(),
+            tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn get(&mut self, id: &UniverseId, key: &NodeHash) -> Option<Arc<[u8]>> {
+        self.tick += 1;
+
+        let Some(entry) = self.entries.get_mut(id).and_then(|proofs| proofs.get_mut(key)) else {
+            self.stats.misses += 1;
+            return None;
+        };
+
+        if self.lru.remove(&entry.tick).is_some() {
+            self.lru.insert(self.tick, (id.clone(), *key));
+        }
+        entry.tick = self.tick;
+        self.stats.hits += 1;
+
+        Some(entry.proof.clone())
+    }
+
+    /// Caches the proof of `key` built against `root`, unless the universe
+    /// has moved to another root since.
+    pub fn insert(&mut self, id: &UniverseId, key: &NodeHash, root: &NodeHash, proof: Arc<[u8]>) {
+        if self.roots.get(id).is_some_and(|current| current != root) {
+            return;
+        }
+        self.roots.insert(id.clone(), *root);
+
+        self.tick += 1;
+        let replaced = self.entries.entry(id.clone()).or_default().insert(*key, Entry { proof, tick: self.tick });
+        if let Some(replaced) = replaced {
+            self.lru.remove(&replaced.tick);
+        }
+        if !self.pinned.contains(id) {
+            self.lru.insert(self.tick, (id.clone(), *key));
+        }
+
+        self.evict();
+    }
+
+    /// Records the new root of a universe, dropping its proofs if it
+    /// changed.
+    pub fn update_root(&mut self, id: &UniverseId, root: &NodeHash) {
+        if self.roots.insert(id.clone(), *root).is_some_and(|previous| previous != *root)
+            && let Some(proofs) = self.entries.remove(id)
+        {
+            for entry in proofs.values() {
+                self.lru.remove(&entry.tick);
+            }
+        }
+    }
+
+    pub fn pin(&mut self, id: &UniverseId) {
+        if self.pinned.insert(id.clone()) {
+            for entry in self.entries.get(id).into_iter().flat_map(HashMap::values) {
+                self.lru.remove(&entry.tick);
+            }
+        }
+    }
+
+    pub fn unpin(&mut self, id: &UniverseId) {
+        if self.pinned.remove(id) {
+            for (key, entry) in self.entries.get(id).into_iter().flatten() {
+                self.lru.insert(entry.tick, (id.clone(), *key));
+            }
+            self.evict();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.values().map(HashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    fn evict(&mut self) {
+        while self.lru.len() > self.capacity {
+            let Some((_, (id, key))) = self.lru.pop_first() else {
+                break;
+            };
+
+            if let Some(proofs) = self.entries.get_mut(&id) {
+                proofs.remove(&key);
+                if proofs.is_empty() {
+                    self.entries.remove(&id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use asset::id::AssetId;
+    use mssmt::tree::NodeHash;
+
+    use super::{CacheStats, ProofCache};
+    use crate::id::UniverseId;
+
+    #[test]
+    fn evict_pin_and_invalidate() {
+        let hot = UniverseId::Asset(AssetId::new([1; 32]));
+        let cold = UniverseId::Asset(AssetId::new([2; 32]));
+        let (root, new_root) = (NodeHash::new([1; 32]), NodeHash::new([2; 32]));
+        let key = |b| NodeHash::new([b; 32]);
+        let proof = |b: u8| -> Arc<[u8]> { Arc::from(vec![b; 4]) };
+
+        let mut cache = ProofCache::new(2);
+        cache.pin(&hot);
+        for b in 0..4 {
+            cache.insert(&hot, &key(b), &root, proof(b));
+            cache.insert(&cold, &key(b), &root, proof(b));
+        }
+
+        // Every pinned proof stays, only the 2 most recent cold ones do.
+        assert_eq!(cache.len(), 6);
+        assert_eq!(cache.get(&hot, &key(0)).as_deref(), Some(&[0; 4][..]));
+        assert!(cache.get(&cold, &key(1)).is_none());
+        assert!(cache.get(&cold, &key(3)).is_some());
+        assert_eq!(cache.stats(), CacheStats { hits: 2, misses: 1 });
+
+        // Proofs built against an older root are dropped, as are the cached
+        // ones once the root changes.
+        cache.update_root(&hot, &new_root);
+        assert!(cache.get(&hot, &key(0)).is_none());
+        cache.insert(&hot, &key(0), &root, proof(0));
+        assert!(cache.get(&hot, &key(0)).is_none());
+        cache.insert(&hot, &key(0), &new_root, proof(9));
+        assert_eq!(cache.get(&hot, &key(0)).as_deref(), Some(&[9; 4][..]));
+        assert_eq!(cache.len(), 3);
+
+        cache.unpin(&hot);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&cold, &key(2)).is_none());
+    }
+
+    #[test]
+    fn evict_after_pin_and_unpin() {
+        let hot = UniverseId::Asset(AssetId::new([1; 32]));
+        let cold = UniverseId::Asset(AssetId::new([2; 32]));
+        let root = NodeHash::new([1; 32]);
+        let key = |b| NodeHash::new([b; 32]);
+        let proof = |b: u8| -> Arc<[u8]> { Arc::from(vec![b; 4]) };
+
+        let mut cache = ProofCache::new(3);
+        cache.insert(&hot, &key(0), &root, proof(0));
+        cache.insert(&hot, &key(1), &root, proof(1));
+        cache.pin(&hot);
+        for b in 2..5 {
+            cache.insert(&cold, &key(b), &root, proof(b));
+        }
+        assert_eq!(cache.len(), 5);
+
+        // Accessed while pinned, hot key 1 is more recent than every cold
+        // proof once unpinned, hot key 0 is the oldest and goes first.
+        assert!(cache.get(&hot, &key(1)).is_some());
+        cache.unpin(&hot);
+        assert_eq!(cache.len(), 3);
+        assert!(cache.get(&hot, &key(0)).is_none());
+        assert!(cache.get(&cold, &key(2)).is_none());
+        assert!(cache.get(&hot, &key(1)).is_some());
+
+        // Unpinned proofs count against the capacity again.
+        cache.insert(&cold, &key(5), &root, proof(5));
+        assert_eq!(cache.len(), 3);
+        assert!(cache.get(&cold, &key(3)).is_none());
+        assert!(cache.get(&cold, &key(4)).is_some());
+        assert!(cache.get(&hot, &key(1)).is_some());
+
+        // Pinning twice or unpinning a universe that isn't pinned changes
+        // nothing.
+        cache.unpin(&cold);
+        cache.pin(&hot);
+        cache.pin(&hot);
+        cache.unpin(&hot);
+        assert_eq!(cache.len(), 3);
+        assert!(!cache.is_empty());
+    }
+}
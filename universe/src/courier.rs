@@ -0,0 +1,192 @@
+//! Delivery of proofs from senders to receivers through couriers.
+//!
+//! Receivers name the courier in their address as a URL like
+//! `universerpc://host:port` or `hashmail://host:port`, the scheme picking
+//! the [`ProofCourier`] registered for it in a [`CourierRouter`]. Integrators
+//! register their own schemes to carry proofs over their infrastructure.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    future::Future,
+    pin::Pin,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{bail, Context};
+use asset::id::AssetId;
+use bitcoin::{OutPoint, secp256k1::XOnlyPublicKey};
+
+pub const UNIVERSE_RPC_SCHEME: &str = "universerpc";
+pub const HASHMAIL_SCHEME: &str = "hashmail";
+
+/// Address of a courier, `<scheme>://<location>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CourierAddr {
+    pub scheme: String,
+    pub location: String,
+}
+
+impl FromStr for CourierAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scheme, location) = s.split_once("://").ok_or("Missing courier scheme".to_string())?;
+        if scheme.is_empty() || !scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) {
+            return Err(format!("Invalid courier scheme {:?}", scheme));
+        }
+
+        Ok(CourierAddr { scheme: scheme.to_ascii_lowercase(), location: location.to_string() })
+    }
+}
+
+impl Display for CourierAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.location)
+    }
+}
+
+/// Identifies the proof of an asset output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProofLocator {
+    pub asset_id: AssetId,
+    pub script_key: XOnlyPublicKey,
+    pub outpoint: OutPoint,
+}
+
+pub type CourierFuture<'a, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+/// Transport of encoded proofs. Futures are boxed so couriers can be picked
+/// at runtime behind a `dyn ProofCourier`.
+pub trait ProofCourier: Send + Sync {
+    /// Hands the proof over for the receiver to pick up.
+    fn deliver<'a>(&'a self, addr: &'a CourierAddr, locator: &'a ProofLocator, proof: &'a [u8]) -> CourierFuture<'a, ()>;
+
+    fn receive<'a>(&'a self, addr: &'a CourierAddr, locator: &'a ProofLocator) -> CourierFuture<'a, Vec<u8>>;
+
+    /// Tells the courier the receiver got the proof, which it may then drop.
+    fn ack<'a>(&'a self, addr: &'a CourierAddr, locator: &'a ProofLocator) -> CourierFuture<'a, ()>;
+}
+
+/// Dispatches to the courier registered for the scheme of the address.
+#[derive(Default, Clone)]
+pub struct CourierRouter {
+    couriers: HashMap<String, Arc<dyn ProofCourier>>,
+}
+
+impl CourierRouter {
+    pub fn register(&mut self, scheme: &str, courier: Arc<dyn ProofCourier>) {
+        self.couriers.insert(scheme.to_ascii_lowercase(), courier);
+    }
+
+    pub fn courier(&self, addr: &CourierAddr) -> anyhow::Result<&dyn ProofCourier> {
+        self.couriers.get(&addr.scheme).map(|courier| courier.as_ref()).with_context(|| format!("No courier for scheme {}", addr.scheme))
+    }
+}
+
+impl ProofCourier for CourierRouter {
+    fn deliver<'a>(&'a self, addr: &'a CourierAddr, locator: &'a ProofLocator, proof: &'a [u8]) -> CourierFuture<'a, ()> {
+        match self.courier(addr) {
+            Ok(courier) => courier.deliver(addr, locator, proof),
+            Err(e) => Box::pin(async { Err(e) }),
+        }
+    }
+
+    fn receive<'a>(&'a self, addr: &'a CourierAddr, locator: &'a ProofLocator) -> CourierFuture<'a, Vec<u8>> {
+        match self.courier(addr) {
+            Ok(courier) => courier.receive(addr, locator),
+            Err(e) => Box::pin(async { Err(e) }),
+        }
+    }
+
+    fn ack<'a>(&'a self, addr: &'a CourierAddr, locator: &'a ProofLocator) -> CourierFuture<'a, ()> {
+        match self.courier(addr) {
+            Ok(courier) => courier.ack(addr, locator),
+            Err(e) => Box::pin(async { Err(e) }),
+        }
+    }
+}
+
+/// Courier keeping proofs in memory until acked, for tests and for sender
+/// and receiver living in the same process.
+#[derive(Default)]
+pub struct MemoryCourier {
+    proofs: Mutex<HashMap<(CourierAddr, ProofLocator), Vec<u8>>>,
+}
+
+impl ProofCourier for MemoryCourier {
+    fn deliver<'a>(&'a self, addr: &'a CourierAddr, locator: &'a ProofLocator, proof: &'a [u8]) -> CourierFuture<'a, ()> {
+        Box::pin(async move {
+            self.proofs.lock().expect("lock poisoned").insert((addr.clone(), *locator), proof.to_vec());
+            Ok(())
+        })
+    }
+
+    fn receive<'a>(&'a self, addr: &'a CourierAddr, locator: &'a ProofLocator) -> CourierFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            match self.proofs.lock().expect("lock poisoned").get(&(addr.clone(), *locator)) {
+                Some(proof) => Ok(proof.clone()),
+                None => bail!("No proof for {} at {}", locator.outpoint, addr),
+            }
+        })
+    }
+
+    fn ack<'a>(&'a self, addr: &'a CourierAddr, locator: &'a ProofLocator) -> CourierFuture<'a, ()> {
+        Box::pin(async move {
+            self.proofs.lock().expect("lock poisoned").remove(&(addr.clone(), *locator));
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        pin::pin,
+        sync::Arc,
+        task::{Context, Poll, Waker},
+    };
+
+    use asset::id::AssetId;
+    use bitcoin::{
+        OutPoint,
+        secp256k1::{Secp256k1, SecretKey},
+    };
+
+    use super::{CourierAddr, CourierRouter, HASHMAIL_SCHEME, MemoryCourier, ProofCourier, ProofLocator};
+
+    /// Polls a future that never waits.
+    fn ready<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future not ready"),
+        }
+    }
+
+    #[test]
+    fn route_by_scheme() {
+        let secp = Secp256k1::new();
+        let locator = ProofLocator {
+            asset_id: AssetId::new([1; 32]),
+            script_key: SecretKey::from_slice(&[1; 32]).unwrap().x_only_public_key(&secp).0,
+            outpoint: OutPoint::null(),
+        };
+
+        let mut router = CourierRouter::default();
+        router.register(HASHMAIL_SCHEME, Arc::new(MemoryCourier::default()));
+
+        let addr: CourierAddr = "HashMail://mailbox.example.com:443".parse().unwrap();
+        assert_eq!(addr.to_string(), "hashmail://mailbox.example.com:443");
+        assert!("mailbox.example.com".parse::<CourierAddr>().is_err());
+
+        ready(router.deliver(&addr, &locator, b"proof")).unwrap();
+        assert_eq!(ready(router.receive(&addr, &locator)).unwrap(), b"proof");
+        ready(router.ack(&addr, &locator)).unwrap();
+        assert!(ready(router.receive(&addr, &locator)).is_err());
+
+        let unknown: CourierAddr = "s3://bucket/proofs".parse().unwrap();
+        assert!(ready(router.deliver(&unknown, &locator, b"proof")).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+//! History of the roots committed for each universe, anchored to the best
+//! block at commit time.
+//!
+//! Records are stored under `h || universe || height`, big endian so the
+//! heights of a universe are scanned in order. Only the last root committed
+//! at a given height is kept. The height of the latest root of each
+//! universe is kept under `l || universe`, so commits and lookups of the
+//! latest root don't scan the history.
+
+use anyhow::{bail, Context};
+use bitcoin::{BlockHash, hashes::Hash};
+use mssmt::{store::KvStore, tree::NodeHash};
+
+use crate::id::UniverseId;
+
+const HISTORY_PREFIX: u8 = b'h';
+const HEAD_PREFIX: u8 = b'l';
+const RECORD_LEN: usize = 72;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RootRecord {
+    pub root: NodeHash,
+    pub sum: u64,
+
+    /// Best block when the root was committed.
+    pub height: u32,
+    pub block_hash: BlockHash,
+}
+
+pub struct RootHistory<S: KvStore> {
+    kv: S,
+}
+
+fn universe_prefix(id: &UniverseId) -> [u8; 33] {
+    let mut prefix = [HISTORY_PREFIX; 33];
+    prefix[1..].copy_from_slice(&id.bytes());
+    prefix
+}
+
+fn head_key(id: &UniverseId) -> [u8; 33] {
+    let mut key = [HEAD_PREFIX; 33];
+    key[1..].copy_from_slice(&id.bytes());
+    key
+}
+
+fn record_key(id: &UniverseId, height: u32) -> [u8; 37] {
+    let mut key = [0; 37];
+    key[..33].copy_from_slice(&universe_prefix(id));
+    key[33..].copy_from_slice(&height.to_be_bytes());
+    key
+}
+
+fn decode_record(key: &[u8], value: &[u8]) -> anyhow::Result<RootRecord> {
+    if key.len() != 37 || value.len() != RECORD_LEN {
+        bail!("Invalid root history record");
+    }
+
+    Ok(RootRecord {
+        root: NodeHash::from_slice(&value[..32]).map_err(anyhow::Error::msg)?,
+        sum: u64::from_be_bytes(value[32..40].try_into()?),
+        height: u32::from_be_bytes(key[33..].try_into()?),
+        block_hash: BlockHash::from_byte_array(value[40..].try_into()?),
+    })
+}
+
+impl<S: KvStore> RootHistory<S> {
+    pub fn new(kv: S) -> Self {
+        RootHistory { kv }
+    }
+
+    /// Records `record` as the root of universe `id` at its height. Heights
+    /// must not go backwards, [`RootHistory::rewind`] first on reorgs.
+    pub fn commit(&mut self, id: &UniverseId, record: &RootRecord) -> anyhow::Result<()> {
+        if let Some(latest) = self.latest_height(id)?
+            && latest > record.height
+        {
+            bail!("Root committed at height {} after one at height {}", record.height, latest);
+        }
+
+        let mut value = Vec::with_capacity(RECORD_LEN);
+        value.extend_from_slice(record.root.as_bytes());
+        value.extend_from_slice(&record.sum.to_be_bytes());
+        value.extend_from_slice(record.block_hash.as_byte_array());
+
+        self.kv.put(&record_key(id, record.height), &value)?;
+        self.kv.put(&head_key(id), &record.height.to_be_bytes())
+    }
+
+    pub fn latest(&self, id: &UniverseId) -> anyhow::Result<Option<RootRecord>> {
+        let Some(height) = self.latest_height(id)? else {
+            return Ok(None);
+        };
+
+        let key = record_key(id, height);
+        let value = self.kv.get(&key)?.context("Missing latest root history record")?;
+
+        decode_record(&key, &value).map(Some)
+    }
+
+    fn latest_height(&self, id: &UniverseId) -> anyhow::Result<Option<u32>> {
+        let Some(head) = self.kv.get(&head_key(id))? else {
+            return Ok(None);
+        };
+
+        Ok(Some(u32::from_be_bytes(head.as_slice().try_into().context("Invalid root history head")?)))
+    }
+
+    /// Returns the root the universe had at block `height`, that is the last
+    /// one committed at or below it. Only heights below the latest root scan
+    /// the history.
+    pub fn root_at_height(&self, id: &UniverseId, height: u32) -> anyhow::Result<Option<RootRecord>> {
+        if let Some(latest) = self.latest(id)?
+            && latest.height <= height
+        {
+            return Ok(Some(latest));
+        }
+
+        let mut found = None;
+
+        for (key, value) in self.kv.scan_prefix(&universe_prefix(id))? {
+            let record = decode_record(&key, &value)?;
+            if record.height > height {
+                break;
+            }
+            found = Some(record);
+        }
+
+        Ok(found)
+    }
+
+    /// Every root committed for the universe, oldest first.
+    pub fn history(&self, id: &UniverseId) -> anyhow::Result<Vec<RootRecord>> {
+        self.kv.scan_prefix(&universe_prefix(id))?.iter().map(|(key, value)| decode_record(key, value)).collect()
+    }
+
+    /// Drops the roots committed above `height`, e.g. after the blocks they
+    /// were anchored to got reorged out.
+    pub fn rewind(&mut self, id: &UniverseId, height: u32) -> anyhow::Result<()> {
+        let mut latest = None;
+
+        for record in self.history(id)? {
+            if record.height > height {
+                self.kv.delete(&record_key(id, record.height))?;
+            } else {
+                latest = Some(record.height);
+            }
+        }
+
+        match latest {
+            Some(latest) => self.kv.put(&head_key(id), &latest.to_be_bytes()),
+            None => self.kv.delete(&head_key(id)),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.kv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asset::id::AssetId;
+    use bitcoin::{BlockHash, hashes::Hash};
+    use mssmt::{store::MemoryKv, tree::NodeHash};
+
+    use super::{RootHistory, RootRecord};
+    use crate::id::UniverseId;
+
+    fn record(b: u8, height: u32) -> RootRecord {
+        RootRecord { root: NodeHash::new([b; 32]), sum: b as u64, height, block_hash: BlockHash::from_byte_array([height as u8; 32]) }
+    }
+
+    #[test]
+    fn root_at_height() {
+        let id = UniverseId::Asset(AssetId::new([1; 32]));
+        let other = UniverseId::Asset(AssetId::new([2; 32]));
+
+        let mut history = RootHistory::new(MemoryKv::default());
+        history.commit(&id, &record(1, 100)).unwrap();
+        history.commit(&id, &record(2, 105)).unwrap();
+        history.commit(&id, &record(3, 105)).unwrap();
+        history.commit(&id, &record(4, 300)).unwrap();
+        history.commit(&other, &record(9, 200)).unwrap();
+
+        assert_eq!(history.root_at_height(&id, 99).unwrap(), None);
+        assert_eq!(history.root_at_height(&id, 100).unwrap(), Some(record(1, 100)));
+        assert_eq!(history.root_at_height(&id, 299).unwrap(), Some(record(3, 105)));
+        assert_eq!(history.latest(&id).unwrap(), Some(record(4, 300)));
+        assert_eq!(history.history(&id).unwrap().len(), 3);
+
+        // Heights can't go backwards without a rewind.
+        assert!(history.commit(&id, &record(5, 200)).is_err());
+        history.rewind(&id, 199).unwrap();
+        history.commit(&id, &record(5, 200)).unwrap();
+        assert_eq!(history.latest(&id).unwrap(), Some(record(5, 200)));
+
+        assert_eq!(history.latest(&other).unwrap(), Some(record(9, 200)));
+
+        history.rewind(&id, 99).unwrap();
+        assert_eq!(history.latest(&id).unwrap(), None);
+        assert_eq!(history.root_at_height(&id, u32::MAX).unwrap(), None);
+        history.commit(&id, &record(6, 50)).unwrap();
+        assert_eq!(history.latest(&id).unwrap(), Some(record(6, 50)));
+    }
+}
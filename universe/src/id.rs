@@ -0,0 +1,168 @@
+use std::{fmt::Display, hash::Hasher, str::FromStr};
+
+use asset::id::AssetId;
+use bitcoin::{hashes::{Hash, sha256}, hex::DisplayHex, key::Parity, secp256k1::PublicKey};
+use mssmt::tree::NodeHash;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+const ASSET_ID_PREFIX: &str = "asset_id";
+const GROUP_KEY_PREFIX: &str = "group_key";
+
+/// Identifies a universe: either the one of a single asset, or the one
+/// shared by all the assets of a group.
+///
+/// Its canonical string form is `asset_id:<hex asset id>` or
+/// `group_key:<hex compressed group key>`.
+///
+/// Group keys are compared by their x coordinate only, like they are keyed
+/// in the multiverse tree, so keys differing in parity are the same
+/// universe. Their string form always holds the even key, parsing one makes
+/// it even.
+#[derive(Clone, Debug)]
+pub enum UniverseId {
+    Asset(AssetId),
+    Group(PublicKey),
+}
+
+impl UniverseId {
+    /// Key of the universe in the multiverse tree: the asset id itself, or
+    /// the sha256 of the BIP-340 serialized group key.
+    pub fn bytes(&self) -> [u8; 32] {
+        match self {
+            Self::Asset(id) => id.to_byte_array(),
+            Self::Group(key) => sha256::Hash::hash(&key.x_only_public_key().0.serialize()).to_byte_array(),
+        }
+    }
+}
+
+/// Even parity key with the x coordinate of `key`, the one standing for all
+/// the keys of a group universe.
+fn even_key(key: &PublicKey) -> PublicKey {
+    key.x_only_public_key().0.public_key(Parity::Even)
+}
+
+impl PartialEq for UniverseId {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Asset(a), Self::Asset(b)) => a == b,
+            (Self::Group(a), Self::Group(b)) => a.x_only_public_key().0 == b.x_only_public_key().0,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for UniverseId {}
+
+impl std::hash::Hash for UniverseId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            Self::Asset(id) => std::hash::Hash::hash(&(0u8, id), state),
+            Self::Group(key) => std::hash::Hash::hash(&(1u8, key.x_only_public_key().0), state),
+        }
+    }
+}
+
+impl From<&UniverseId> for NodeHash {
+    fn from(id: &UniverseId) -> Self {
+        NodeHash::new(id.bytes())
+    }
+}
+
+impl FromStr for UniverseId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, value) = s.split_once(':').ok_or("Missing universe id prefix".to_string())?;
+
+        match prefix {
+            ASSET_ID_PREFIX => Ok(Self::Asset(value.parse()?)),
+            GROUP_KEY_PREFIX => {
+                if value.len() != 66 {
+                    return Err("Invalid group key length".to_string());
+                }
+                let key = value.parse().map_err(|e| format!("Invalid group key {:?}", e))?;
+
+                Ok(Self::Group(even_key(&key)))
+            },
+            _ => Err(format!("Unknown universe id prefix {:?}", prefix)),
+        }
+    }
+}
+
+impl Display for UniverseId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Asset(id) => write!(f, "{}:{}", ASSET_ID_PREFIX, id),
+            Self::Group(key) => write!(f, "{}:{}", GROUP_KEY_PREFIX, even_key(key).serialize().as_hex()),
+        }
+    }
+}
+
+/// Serialized in its canonical string form.
+impl Serialize for UniverseId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for UniverseId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use asset::id::AssetId;
+    use bitcoin::secp256k1::Secp256k1;
+    use mssmt::tree::NodeHash;
+
+    use super::UniverseId;
+
+    // Generator point of secp256k1.
+    const GROUP_KEY: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    #[test]
+    fn round_trip() {
+        let asset = UniverseId::Asset(AssetId::new([0xab; 32]));
+        let s = asset.to_string();
+        assert_eq!(s, format!("asset_id:{}", "ab".repeat(32)));
+        assert_eq!(UniverseId::from_str(&s).unwrap(), asset);
+        assert_eq!(asset.bytes(), [0xab; 32]);
+        assert_eq!(NodeHash::from(&asset), NodeHash::new([0xab; 32]));
+
+        let group = UniverseId::from_str(&format!("group_key:{}", GROUP_KEY)).unwrap();
+        assert_eq!(group.to_string(), format!("group_key:{}", GROUP_KEY));
+        assert_ne!(group.bytes(), [0; 32]);
+
+        // Equal group ids have the same string form whatever their parity.
+        let odd = UniverseId::from_str(&format!("group_key:03{}", &GROUP_KEY[2..])).unwrap();
+        assert_eq!(odd, group);
+        assert_eq!(odd.to_string(), group.to_string());
+        assert!(matches!(odd, UniverseId::Group(key) if key.serialize()[0] == 0x02));
+
+        let UniverseId::Group(key) = group else { unreachable!() };
+        let odd = UniverseId::Group(key.negate(&Secp256k1::verification_only()));
+        assert_eq!(odd.to_string(), group.to_string());
+        assert_eq!(serde_json::to_string(&odd).unwrap(), serde_json::to_string(&group).unwrap());
+        assert_eq!(odd.bytes(), group.bytes());
+    }
+
+    #[test]
+    fn invalid() {
+        for s in [
+            "",
+            "ab".repeat(32).as_str(),
+            "asset_id:abcd",
+            "asset_id:",
+            "group_key:79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            format!("group_key:04{}", &GROUP_KEY[2..]).as_str(),
+            format!("group:{}", GROUP_KEY).as_str(),
+        ] {
+            assert!(UniverseId::from_str(s).is_err(), "{:?} should not parse", s);
+        }
+    }
+}
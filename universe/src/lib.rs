@@ -0,0 +1,12 @@
+pub mod audit;
+pub mod blob;
+pub mod cache;
+pub mod courier;
+pub mod history;
+pub mod id;
+pub mod metrics;
+pub mod policy;
+pub mod replay;
+pub mod scheduler;
+pub mod shutdown;
+pub mod supply;
@@ -0,0 +1,12 @@
+//! Universe metrics, recorded through the `metrics` facade when the
+//! `metrics` feature is enabled.
+
+pub const INGEST_CONFLICTS: &str = "universe_ingest_conflicts_total";
+
+#[cfg(feature = "metrics")]
+pub(crate) fn record_ingest_conflict(kind: &'static str) {
+    metrics::counter!(INGEST_CONFLICTS, "kind" => kind).increment(1);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn record_ingest_conflict(_kind: &'static str) {}
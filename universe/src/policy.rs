@@ -0,0 +1,110 @@
+//! Scoping of the universes a server accepts proofs for.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::id::UniverseId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofType {
+    Issuance,
+    Transfer,
+}
+
+/// Which universes, and which kind of proofs within them, are accepted.
+/// The deny list always wins over the allow list.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    /// When set, only these universes are accepted.
+    allow: Option<HashSet<UniverseId>>,
+
+    deny: HashSet<UniverseId>,
+
+    /// Only accept issuance proofs, e.g. for a server acting as an asset
+    /// registry rather than tracking transfers.
+    issuance_only: bool,
+}
+
+impl Policy {
+    /// Policy accepting every proof of every universe.
+    pub fn accept_all() -> Self {
+        Self::default()
+    }
+
+    /// Policy only accepting the given universes.
+    pub fn allow_only<I: IntoIterator<Item = UniverseId>>(ids: I) -> Self {
+        Policy { allow: Some(ids.into_iter().collect()), ..Self::default() }
+    }
+
+    pub fn deny(mut self, id: UniverseId) -> Self {
+        self.deny.insert(id);
+        self
+    }
+
+    pub fn issuance_only(mut self) -> Self {
+        self.issuance_only = true;
+        self
+    }
+
+    pub fn accepts(&self, id: &UniverseId, proof_type: ProofType) -> bool {
+        if self.issuance_only && proof_type != ProofType::Issuance {
+            return false;
+        }
+
+        if self.deny.contains(id) {
+            return false;
+        }
+
+        self.allow.as_ref().is_none_or(|allow| allow.contains(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asset::{id::AssetId, script_key::nums_key};
+    use bitcoin::secp256k1::Parity;
+
+    use super::{Policy, ProofType};
+    use crate::id::UniverseId;
+
+    fn id(b: u8) -> UniverseId {
+        UniverseId::Asset(AssetId::new([b; 32]))
+    }
+
+    #[test]
+    fn allow_and_deny() {
+        let all = Policy::accept_all().deny(id(2));
+        assert!(all.accepts(&id(1), ProofType::Transfer));
+        assert!(!all.accepts(&id(2), ProofType::Issuance));
+
+        let some = Policy::allow_only([id(1), id(2)]).deny(id(2));
+        assert!(some.accepts(&id(1), ProofType::Issuance));
+        assert!(!some.accepts(&id(2), ProofType::Issuance));
+        assert!(!some.accepts(&id(3), ProofType::Issuance));
+
+        // Flipping the parity of a denied group key doesn't get it through.
+        let group = |parity| UniverseId::Group(nums_key().public_key(parity));
+        let groups = Policy::accept_all().deny(group(Parity::Even));
+        assert!(!groups.accepts(&group(Parity::Odd), ProofType::Issuance));
+        assert!(!Policy::allow_only([group(Parity::Odd)]).deny(group(Parity::Even)).accepts(&group(Parity::Odd), ProofType::Issuance));
+
+        let issuance = Policy::accept_all().issuance_only();
+        assert!(issuance.accepts(&id(1), ProofType::Issuance));
+        assert!(!issuance.accepts(&id(1), ProofType::Transfer));
+    }
+
+    #[test]
+    fn from_config() {
+        let config = format!(r#"{{"allow": ["asset_id:{}"], "issuance_only": true}}"#, "01".repeat(32));
+        let policy: Policy = serde_json::from_str(&config).unwrap();
+
+        assert_eq!(policy, Policy::allow_only([id(1)]).issuance_only());
+
+        let json = serde_json::to_string(&policy).unwrap();
+        assert_eq!(serde_json::from_str::<Policy>(&json).unwrap(), policy);
+        assert_eq!(serde_json::from_str::<Policy>("{}").unwrap(), Policy::accept_all());
+    }
+}
@@ -0,0 +1,457 @@
+//! Detection of proofs presented twice, or for leaves already spent.
+//!
+//! Ingested proofs are recorded under `p || outpoint || script key` along
+//! with the leaves they spend, and those leaves under `s || outpoint ||
+//! script key` with the leaf of the spending proof. Proofs spending a leaf
+//! already spent by another output are double spends, they are kept under
+//! `q || outpoint || script key` until an operator resolves the conflict.
+//! Accepting one drops the losing spend along with every proof descending
+//! from it.
+
+use std::{
+    fmt::Display,
+    sync::mpsc::{Receiver, Sender, channel},
+};
+
+use anyhow::bail;
+use bitcoin::{
+    OutPoint, Txid,
+    hashes::{Hash, sha256},
+    secp256k1::XOnlyPublicKey,
+};
+use mssmt::store::KvStore;
+
+use crate::metrics;
+
+const PROOF_PREFIX: u8 = b'p';
+const SPENT_PREFIX: u8 = b's';
+const QUARANTINE_PREFIX: u8 = b'q';
+const LEAF_ID_LEN: usize = 68;
+
+fn encode_outpoint(outpoint: &OutPoint) -> [u8; 36] {
+    let mut bytes = [0; 36];
+    bytes[..32].copy_from_slice(outpoint.txid.as_byte_array());
+    bytes[32..].copy_from_slice(&outpoint.vout.to_be_bytes());
+    bytes
+}
+
+fn decode_outpoint(bytes: &[u8]) -> anyhow::Result<OutPoint> {
+    if bytes.len() != 36 {
+        bail!("Invalid outpoint length {}", bytes.len());
+    }
+
+    Ok(OutPoint { txid: Txid::from_byte_array(bytes[..32].try_into()?), vout: u32::from_be_bytes(bytes[32..].try_into()?) })
+}
+
+/// Asset output a proof is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LeafId {
+    pub outpoint: OutPoint,
+    pub script_key: XOnlyPublicKey,
+}
+
+impl LeafId {
+    fn encode(&self) -> [u8; LEAF_ID_LEN] {
+        let mut bytes = [0; LEAF_ID_LEN];
+        bytes[..36].copy_from_slice(&encode_outpoint(&self.outpoint));
+        bytes[36..].copy_from_slice(&self.script_key.serialize());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() != LEAF_ID_LEN {
+            bail!("Invalid leaf id length {}", bytes.len());
+        }
+
+        Ok(LeafId {
+            outpoint: decode_outpoint(&bytes[..36])?,
+            script_key: XOnlyPublicKey::from_slice(&bytes[36..]).map_err(anyhow::Error::msg)?,
+        })
+    }
+
+    fn key(&self, prefix: u8) -> [u8; LEAF_ID_LEN + 1] {
+        let mut key = [prefix; LEAF_ID_LEN + 1];
+        key[1..].copy_from_slice(&self.encode());
+        key
+    }
+}
+
+/// Proof about to be ingested.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IngestedProof {
+    pub leaf: LeafId,
+    pub hash: sha256::Hash,
+
+    /// Leaves spent by the transfer the proof is for, empty for a mint.
+    pub spends: Vec<LeafId>,
+}
+
+impl IngestedProof {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32 + LEAF_ID_LEN * (1 + self.spends.len()));
+        bytes.extend_from_slice(self.hash.as_byte_array());
+        bytes.extend_from_slice(&self.leaf.encode());
+        for spent in &self.spends {
+            bytes.extend_from_slice(&spent.encode());
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() < 32 + LEAF_ID_LEN || !(bytes.len() - 32).is_multiple_of(LEAF_ID_LEN) {
+            bail!("Invalid proof record length {}", bytes.len());
+        }
+
+        let mut leaves = bytes[32..].chunks(LEAF_ID_LEN).map(LeafId::decode);
+
+        Ok(IngestedProof {
+            hash: sha256::Hash::from_byte_array(bytes[..32].try_into()?),
+            leaf: leaves.next().expect("length checked")?,
+            spends: leaves.collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IngestConflict {
+    /// A proof was already ingested for the leaf, `existing` equals the hash
+    /// of the new one when it is replayed as is.
+    Duplicate { leaf: LeafId, existing: sha256::Hash },
+
+    /// The leaf was already spent by the output at `spent_by`.
+    AlreadySpent { leaf: LeafId, spent_by: OutPoint },
+
+    /// The proof spends `leaf`, already spent by the output at `spent_by`.
+    /// The proof is quarantined until [`ReplayGuard::resolve`] is called.
+    DoubleSpend { leaf: LeafId, spent_by: OutPoint, conflicting: OutPoint },
+}
+
+impl IngestConflict {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Duplicate { .. } => "duplicate",
+            Self::AlreadySpent { .. } => "already_spent",
+            Self::DoubleSpend { .. } => "double_spend",
+        }
+    }
+}
+
+impl Display for IngestConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Duplicate { leaf, existing } => {
+                write!(f, "Proof for {}:{} already ingested as {}", leaf.outpoint, leaf.script_key, existing)
+            },
+            Self::AlreadySpent { leaf, spent_by } => {
+                write!(f, "Leaf {}:{} already spent by {}", leaf.outpoint, leaf.script_key, spent_by)
+            },
+            Self::DoubleSpend { leaf, spent_by, conflicting } => {
+                write!(f, "Leaf {}:{} spent by both {} and {}", leaf.outpoint, leaf.script_key, spent_by, conflicting)
+            },
+        }
+    }
+}
+
+impl std::error::Error for IngestConflict {}
+
+/// Decision of an operator on a quarantined proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// The quarantined proof holds the valid spend, it replaces the one
+    /// previously recorded as spending its inputs. Proofs spending the
+    /// output of the replaced one are dropped too.
+    Accept,
+    Reject,
+}
+
+pub struct ReplayGuard<S: KvStore> {
+    kv: S,
+    subscribers: Vec<Sender<IngestConflict>>,
+}
+
+impl<S: KvStore> ReplayGuard<S> {
+    pub fn new(kv: S) -> Self {
+        ReplayGuard { kv, subscribers: Vec::new() }
+    }
+
+    /// Returns a receiver getting every conflict raised on ingestion.
+    /// Subscribers are dropped once their receiver is.
+    pub fn subscribe_conflicts(&mut self) -> Receiver<IngestConflict> {
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+
+        rx
+    }
+
+    /// Records `proof` unless it conflicts with the ones already ingested,
+    /// the [`IngestConflict`] is then returned as the error. Double spends
+    /// are quarantined, unless another proof is already quarantined for the
+    /// same leaf.
+    pub fn ingest(&mut self, proof: &IngestedProof) -> anyhow::Result<()> {
+        if let Some(conflict) = self.check(proof)? {
+            if let IngestConflict::DoubleSpend { .. } = conflict {
+                if let Some(value) = self.kv.get(&proof.leaf.key(QUARANTINE_PREFIX))? {
+                    let existing = IngestedProof::decode(&value)?;
+                    if existing.hash != proof.hash {
+                        bail!("Proof for {}:{} already quarantined as {}", proof.leaf.outpoint, proof.leaf.script_key, existing.hash);
+                    }
+                }
+
+                self.kv.put(&proof.leaf.key(QUARANTINE_PREFIX), &proof.encode())?;
+            }
+
+            metrics::record_ingest_conflict(conflict.kind());
+            self.subscribers.retain(|tx| tx.send(conflict.clone()).is_ok());
+
+            return Err(conflict.into());
+        }
+
+        self.record(proof)
+    }
+
+    fn record(&mut self, proof: &IngestedProof) -> anyhow::Result<()> {
+        self.kv.put(&proof.leaf.key(PROOF_PREFIX), &proof.encode())?;
+
+        let spender = proof.leaf.encode();
+        for spent in &proof.spends {
+            self.kv.put(&spent.key(SPENT_PREFIX), &spender)?;
+        }
+
+        Ok(())
+    }
+
+    /// Drops the proof of `leaf` along with the spends it recorded, then
+    /// the proofs descending from it.
+    fn forget(&mut self, leaf: &LeafId) -> anyhow::Result<()> {
+        let mut next = Some(*leaf);
+
+        while let Some(leaf) = next {
+            next = self.spender(&leaf)?;
+
+            let Some(proof) = self.proof(&leaf)? else {
+                continue;
+            };
+            for spent in &proof.spends {
+                if self.spender(spent)? == Some(leaf) {
+                    self.kv.delete(&spent.key(SPENT_PREFIX))?;
+                }
+            }
+            self.kv.delete(&leaf.key(PROOF_PREFIX))?;
+        }
+
+        Ok(())
+    }
+
+    /// Proofs held back as double spends, each with its current conflict.
+    pub fn quarantined(&self) -> anyhow::Result<Vec<(IngestedProof, Option<IngestConflict>)>> {
+        let mut quarantined = Vec::new();
+
+        for (_, value) in self.kv.scan_prefix(&[QUARANTINE_PREFIX])? {
+            let proof = IngestedProof::decode(&value)?;
+            let conflict = self.check(&proof)?;
+            quarantined.push((proof, conflict));
+        }
+
+        Ok(quarantined)
+    }
+
+    /// Releases the quarantined proof for `leaf`. When accepted, the proofs
+    /// it conflicts with and their descendants are dropped, and it is
+    /// recorded in their place.
+    pub fn resolve(&mut self, leaf: &LeafId, resolution: Resolution) -> anyhow::Result<()> {
+        let key = leaf.key(QUARANTINE_PREFIX);
+        let Some(value) = self.kv.get(&key)? else {
+            bail!("No quarantined proof for {}:{}", leaf.outpoint, leaf.script_key);
+        };
+
+        if resolution == Resolution::Accept {
+            let proof = IngestedProof::decode(&value)?;
+            if let Some(existing) = self.proof_hash(&proof.leaf)? {
+                bail!("Proof for {}:{} already ingested as {}", leaf.outpoint, leaf.script_key, existing);
+            }
+
+            for spent in &proof.spends {
+                if let Some(spender) = self.spender(spent)?
+                    && spender != proof.leaf
+                {
+                    self.forget(&spender)?;
+                }
+            }
+            self.record(&proof)?;
+        }
+
+        self.kv.delete(&key)
+    }
+
+    /// Returns the conflict ingesting `proof` would raise, if any.
+    pub fn check(&self, proof: &IngestedProof) -> anyhow::Result<Option<IngestConflict>> {
+        if let Some(existing) = self.proof_hash(&proof.leaf)? {
+            return Ok(Some(IngestConflict::Duplicate { leaf: proof.leaf, existing }));
+        }
+
+        if let Some(spent_by) = self.spent_by(&proof.leaf)? {
+            return Ok(Some(IngestConflict::AlreadySpent { leaf: proof.leaf, spent_by }));
+        }
+
+        for spent in &proof.spends {
+            if let Some(spender) = self.spender(spent)?
+                && spender != proof.leaf
+            {
+                return Ok(Some(IngestConflict::DoubleSpend { leaf: *spent, spent_by: spender.outpoint, conflicting: proof.leaf.outpoint }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn proof(&self, leaf: &LeafId) -> anyhow::Result<Option<IngestedProof>> {
+        self.kv.get(&leaf.key(PROOF_PREFIX))?.map(|value| IngestedProof::decode(&value)).transpose()
+    }
+
+    /// Hash of the proof ingested for `leaf`.
+    pub fn proof_hash(&self, leaf: &LeafId) -> anyhow::Result<Option<sha256::Hash>> {
+        Ok(self.proof(leaf)?.map(|proof| proof.hash))
+    }
+
+    fn spender(&self, leaf: &LeafId) -> anyhow::Result<Option<LeafId>> {
+        self.kv.get(&leaf.key(SPENT_PREFIX))?.map(|value| LeafId::decode(&value)).transpose()
+    }
+
+    /// Outpoint of the output spending `leaf`.
+    pub fn spent_by(&self, leaf: &LeafId) -> anyhow::Result<Option<OutPoint>> {
+        Ok(self.spender(leaf)?.map(|spender| spender.outpoint))
+    }
+
+    pub fn into_inner(self) -> S {
+        self.kv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        OutPoint, Txid,
+        hashes::{Hash, sha256},
+        secp256k1::{Secp256k1, SecretKey},
+    };
+    use mssmt::store::MemoryKv;
+
+    use super::{IngestConflict, IngestedProof, LeafId, ReplayGuard, Resolution};
+
+    fn leaf(b: u8) -> LeafId {
+        let secp = Secp256k1::new();
+        LeafId {
+            outpoint: OutPoint { txid: Txid::from_byte_array([b; 32]), vout: b as u32 },
+            script_key: SecretKey::from_slice(&[b; 32]).unwrap().x_only_public_key(&secp).0,
+        }
+    }
+
+    fn proof(leaf: LeafId, spends: Vec<LeafId>) -> IngestedProof {
+        IngestedProof { leaf, hash: sha256::Hash::hash(&leaf.outpoint.vout.to_be_bytes()), spends }
+    }
+
+    #[test]
+    fn rejects_duplicates_and_spent_leaves() {
+        let mut guard = ReplayGuard::new(MemoryKv::default());
+
+        let mint = proof(leaf(1), vec![]);
+        guard.ingest(&mint).unwrap();
+
+        let err = guard.ingest(&mint).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<IngestConflict>(),
+            Some(&IngestConflict::Duplicate { leaf: leaf(1), existing: mint.hash })
+        );
+
+        let transfer = proof(leaf(2), vec![leaf(3)]);
+        guard.ingest(&transfer).unwrap();
+        assert_eq!(guard.spent_by(&leaf(3)).unwrap(), Some(leaf(2).outpoint));
+        assert_eq!(guard.spent_by(&leaf(1)).unwrap(), None);
+
+        // A proof for the already spent leaf 3 shows up late.
+        let err = guard.ingest(&proof(leaf(3), vec![])).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<IngestConflict>(),
+            Some(&IngestConflict::AlreadySpent { leaf: leaf(3), spent_by: leaf(2).outpoint })
+        );
+        assert_eq!(guard.proof_hash(&leaf(3)).unwrap(), None);
+    }
+
+    #[test]
+    fn quarantines_double_spends() {
+        let mut guard = ReplayGuard::new(MemoryKv::default());
+        let rx = guard.subscribe_conflicts();
+
+        guard.ingest(&proof(leaf(1), vec![])).unwrap();
+        guard.ingest(&proof(leaf(2), vec![leaf(1), leaf(6)])).unwrap();
+
+        // Leaf 1 spent a second time, by leaf 3.
+        let double_spend = proof(leaf(3), vec![leaf(4), leaf(1)]);
+        let conflict = IngestConflict::DoubleSpend { leaf: leaf(1), spent_by: leaf(2).outpoint, conflicting: leaf(3).outpoint };
+        let err = guard.ingest(&double_spend).unwrap_err();
+        assert_eq!(err.downcast_ref::<IngestConflict>(), Some(&conflict));
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![conflict.clone()]);
+
+        assert_eq!(guard.quarantined().unwrap(), vec![(double_spend.clone(), Some(conflict))]);
+        assert_eq!(guard.proof_hash(&leaf(3)).unwrap(), None);
+
+        assert!(guard.resolve(&leaf(2), Resolution::Reject).is_err());
+        guard.resolve(&leaf(3), Resolution::Accept).unwrap();
+        assert!(guard.quarantined().unwrap().is_empty());
+        assert_eq!(guard.proof_hash(&leaf(3)).unwrap(), Some(double_spend.hash));
+        assert_eq!(guard.spent_by(&leaf(1)).unwrap(), Some(leaf(3).outpoint));
+        assert_eq!(guard.spent_by(&leaf(4)).unwrap(), Some(leaf(3).outpoint));
+
+        // The losing spend is dropped, freeing the other leaves it spent.
+        assert_eq!(guard.proof_hash(&leaf(2)).unwrap(), None);
+        assert_eq!(guard.spent_by(&leaf(6)).unwrap(), None);
+        assert_eq!(guard.proof_hash(&leaf(1)).unwrap(), Some(proof(leaf(1), vec![]).hash));
+
+        // Rejected proofs are dropped without being recorded.
+        let rejected = proof(leaf(5), vec![leaf(4)]);
+        assert!(guard.ingest(&rejected).is_err());
+        guard.resolve(&leaf(5), Resolution::Reject).unwrap();
+        assert!(guard.quarantined().unwrap().is_empty());
+        assert_eq!(guard.proof_hash(&leaf(5)).unwrap(), None);
+    }
+
+    #[test]
+    fn keeps_the_first_quarantined_proof() {
+        let mut guard = ReplayGuard::new(MemoryKv::default());
+
+        guard.ingest(&proof(leaf(2), vec![leaf(1)])).unwrap();
+        let first = proof(leaf(3), vec![leaf(1)]);
+        assert!(guard.ingest(&first).unwrap_err().downcast_ref::<IngestConflict>().is_some());
+
+        // Another double spend for leaf 3 doesn't replace the quarantined
+        // one, replaying the quarantined one does.
+        let second = IngestedProof { hash: sha256::Hash::hash(b"second"), ..proof(leaf(3), vec![leaf(1), leaf(4)]) };
+        let err = guard.ingest(&second).unwrap_err();
+        assert!(err.downcast_ref::<IngestConflict>().is_none());
+        assert!(guard.ingest(&first).unwrap_err().downcast_ref::<IngestConflict>().is_some());
+        assert_eq!(guard.quarantined().unwrap().into_iter().map(|(proof, _)| proof).collect::<Vec<_>>(), vec![first]);
+    }
+
+    #[test]
+    fn accept_drops_descendants_of_the_losing_spend() {
+        let mut guard = ReplayGuard::new(MemoryKv::default());
+
+        // Leaf 1 spent by leaf 2, itself spent by leaf 7, spent by leaf 8.
+        guard.ingest(&proof(leaf(2), vec![leaf(1)])).unwrap();
+        guard.ingest(&proof(leaf(7), vec![leaf(2), leaf(5)])).unwrap();
+        guard.ingest(&proof(leaf(8), vec![leaf(7)])).unwrap();
+
+        assert!(guard.ingest(&proof(leaf(3), vec![leaf(1)])).is_err());
+        guard.resolve(&leaf(3), Resolution::Accept).unwrap();
+        assert_eq!(guard.spent_by(&leaf(1)).unwrap(), Some(leaf(3).outpoint));
+
+        for dropped in [leaf(2), leaf(7), leaf(8)] {
+            assert_eq!(guard.proof_hash(&dropped).unwrap(), None);
+            assert_eq!(guard.spent_by(&dropped).unwrap(), None);
+        }
+        assert_eq!(guard.spent_by(&leaf(5)).unwrap(), None);
+
+        // The outputs of the dropped proofs can be ingested again.
+        guard.ingest(&proof(leaf(7), vec![leaf(3)])).unwrap();
+    }
+}
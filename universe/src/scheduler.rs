@@ -0,0 +1,241 @@
+//! Persisted schedules of the background jobs of a daemon, e.g. universe
+//! sync, proof re-delivery, reorg checks or garbage collection.
+//!
+//! Jobs are stored under `j || name`. The scheduler doesn't own a clock: the
+//! daemon loop passes the current time to [`Scheduler::due`] or
+//! [`Scheduler::run_due`] and sleeps until [`Scheduler::next_wakeup`].
+//! Runs are delayed by a jitter derived from the job name, spreading jobs
+//! scheduled together, and failed runs are retried with exponential backoff.
+
+use anyhow::bail;
+use bitcoin::hashes::{Hash, sha256};
+use mssmt::store::KvStore;
+use tlv::{Reader, Writer};
+
+const JOB_PREFIX: u8 = b'j';
+
+const INTERVAL_TYPE: u64 = 0;
+const RETRY_DELAY_TYPE: u64 = 2;
+const MAX_BACKOFF_TYPE: u64 = 4;
+const JITTER_TYPE: u64 = 6;
+const NEXT_RUN_TYPE: u64 = 8;
+const FAILURES_TYPE: u64 = 10;
+
+/// Durations are in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JobSchedule {
+    pub interval: u64,
+
+    /// Delay before the first retry of a failed run, doubled on every
+    /// further failure up to `max_backoff`.
+    pub retry_delay: u64,
+    pub max_backoff: u64,
+
+    /// Upper bound of the delay added to every run.
+    pub jitter: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Job {
+    pub schedule: JobSchedule,
+
+    /// Unix time in seconds of the next run.
+    pub next_run: u64,
+
+    /// Failed runs since the last successful one.
+    pub failures: u32,
+}
+
+impl Job {
+    fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer
+            .put(INTERVAL_TYPE, &self.schedule.interval)
+            .and_then(|w| w.put(RETRY_DELAY_TYPE, &self.schedule.retry_delay))
+            .and_then(|w| w.put(MAX_BACKOFF_TYPE, &self.schedule.max_backoff))
+            .and_then(|w| w.put(JITTER_TYPE, &self.schedule.jitter))
+            .and_then(|w| w.put(NEXT_RUN_TYPE, &self.next_run))
+            .and_then(|w| w.put(FAILURES_TYPE, &self.failures))
+            .expect("types are written in order");
+
+        writer.finish()
+    }
+
+    fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        let records = Reader::new(bytes).records()?;
+        if records.iter().map(|r| r.tlv_type).ne([INTERVAL_TYPE, RETRY_DELAY_TYPE, MAX_BACKOFF_TYPE, JITTER_TYPE, NEXT_RUN_TYPE, FAILURES_TYPE]) {
+            bail!("Invalid job records");
+        }
+
+        Ok(Job {
+            schedule: JobSchedule {
+                interval: records[0].decode()?,
+                retry_delay: records[1].decode()?,
+                max_backoff: records[2].decode()?,
+                jitter: records[3].decode()?,
+            },
+            next_run: records[4].decode()?,
+            failures: records[5].decode()?,
+        })
+    }
+}
+
+fn job_key(name: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + name.len());
+    key.push(JOB_PREFIX);
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+/// Delay in `0..=max` picked from the job name and the time it is computed
+/// at, so it is stable across restarts but differs between runs.
+fn jitter(name: &str, now: u64, max: u64) -> u64 {
+    if max == 0 {
+        return 0;
+    }
+
+    let hash = sha256::Hash::hash(&[name.as_bytes(), &now.to_be_bytes()].concat());
+    u64::from_be_bytes(hash[..8].try_into().expect("8 bytes")) % (max + 1)
+}
+
+pub struct Scheduler<S: KvStore> {
+    kv: S,
+}
+
+impl<S: KvStore> Scheduler<S> {
+    pub fn new(kv: S) -> Self {
+        Scheduler { kv }
+    }
+
+    /// Schedules `name`, its first run being due after a jitter. Scheduling
+    /// an existing job replaces its schedule and resets its failures.
+    pub fn schedule(&mut self, name: &str, schedule: JobSchedule, now: u64) -> anyhow::Result<Job> {
+        if name.is_empty() {
+            bail!("Job without a name");
+        }
+        if schedule.interval == 0 {
+            bail!("Job {} scheduled with a zero interval", name);
+        }
+
+        let job = Job { schedule, next_run: now.saturating_add(jitter(name, now, schedule.jitter)), failures: 0 };
+        self.kv.put(&job_key(name), &job.encode())?;
+
+        Ok(job)
+    }
+
+    pub fn unschedule(&mut self, name: &str) -> anyhow::Result<()> {
+        self.kv.delete(&job_key(name))
+    }
+
+    pub fn job(&self, name: &str) -> anyhow::Result<Option<Job>> {
+        self.kv.get(&job_key(name))?.map(|bytes| Job::decode(&bytes)).transpose()
+    }
+
+    /// Every scheduled job, by name.
+    pub fn jobs(&self) -> anyhow::Result<Vec<(String, Job)>> {
+        self.kv
+            .scan_prefix(&[JOB_PREFIX])?
+            .iter()
+            .map(|(key, value)| Ok((String::from_utf8(key[1..].to_vec())?, Job::decode(value)?)))
+            .collect()
+    }
+
+    /// Names of the jobs due at `now`, the most overdue first.
+    pub fn due(&self, now: u64) -> anyhow::Result<Vec<String>> {
+        let mut due: Vec<_> = self.jobs()?.into_iter().filter(|(_, job)| job.next_run <= now).collect();
+        due.sort_by_key(|(_, job)| job.next_run);
+
+        Ok(due.into_iter().map(|(name, _)| name).collect())
+    }
+
+    /// Time of the next run of any job.
+    pub fn next_wakeup(&self) -> anyhow::Result<Option<u64>> {
+        Ok(self.jobs()?.iter().map(|(_, job)| job.next_run).min())
+    }
+
+    /// Records the outcome of a run of `name` finished at `now` and
+    /// schedules the next one.
+    pub fn complete(&mut self, name: &str, now: u64, success: bool) -> anyhow::Result<Job> {
+        let Some(mut job) = self.job(name)? else {
+            bail!("Unknown job {}", name);
+        };
+
+        let delay = if success {
+            job.failures = 0;
+            job.schedule.interval
+        } else {
+            job.failures = job.failures.saturating_add(1);
+            let backoff = job.schedule.retry_delay.saturating_mul(1 << (job.failures - 1).min(63));
+            backoff.min(job.schedule.max_backoff)
+        };
+        job.next_run = now.saturating_add(delay).saturating_add(jitter(name, now, job.schedule.jitter));
+        self.kv.put(&job_key(name), &job.encode())?;
+
+        Ok(job)
+    }
+
+    /// Runs every job due at `now` and schedules their next run, returning
+    /// the names of the jobs which failed along with their error.
+    pub fn run_due<F>(&mut self, now: u64, mut run: F) -> anyhow::Result<Vec<(String, anyhow::Error)>>
+    where
+        F: FnMut(&str) -> anyhow::Result<()>,
+    {
+        let mut failed = Vec::new();
+
+        for name in self.due(now)? {
+            let result = run(&name);
+            self.complete(&name, now, result.is_ok())?;
+            if let Err(e) = result {
+                failed.push((name, e));
+            }
+        }
+
+        Ok(failed)
+    }
+
+    pub fn into_inner(self) -> S {
+        self.kv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+    use mssmt::store::MemoryKv;
+
+    use super::{JobSchedule, Scheduler};
+
+    #[test]
+    fn run_with_backoff() {
+        let mut scheduler = Scheduler::new(MemoryKv::default());
+        let sync = JobSchedule { interval: 600, retry_delay: 10, max_backoff: 35, jitter: 0 };
+        let gc = JobSchedule { interval: 3600, retry_delay: 60, max_backoff: 600, jitter: 30 };
+        assert!(scheduler.schedule("gc", JobSchedule { interval: 0, ..gc }, 1_000).is_err());
+
+        scheduler.schedule("sync", sync, 1_000).unwrap();
+        let first_gc = scheduler.schedule("gc", gc, 1_000).unwrap().next_run;
+        assert!((1_000..=1_030).contains(&first_gc));
+        assert_eq!(scheduler.due(1_000).unwrap(), vec!["sync"]);
+        assert_eq!(scheduler.due(1_030).unwrap().len(), 2);
+
+        // Sync keeps failing: retried after 10, 20, then 35 seconds.
+        let mut now = 1_000;
+        for delay in [10, 20, 35, 35] {
+            let failed = scheduler.run_due(now, |name| if name == "sync" { bail!("Peer unreachable") } else { Ok(()) }).unwrap();
+            assert_eq!(failed.len(), 1);
+            let job = scheduler.job("sync").unwrap().unwrap();
+            assert_eq!(job.next_run, now + delay);
+            now = job.next_run;
+        }
+        assert_eq!(scheduler.job("sync").unwrap().unwrap().failures, 4);
+
+        let job = scheduler.complete("sync", now, true).unwrap();
+        assert_eq!((job.next_run, job.failures), (now + 600, 0));
+        assert!(scheduler.complete("reorg", now, true).is_err());
+
+        // Schedules survive a restart.
+        let scheduler = Scheduler::new(scheduler.into_inner());
+        assert_eq!(scheduler.jobs().unwrap().len(), 2);
+        assert!(scheduler.next_wakeup().unwrap().unwrap() <= now + 600);
+    }
+}
@@ -0,0 +1,112 @@
+//! Coordinated shutdown: long running loops poll a [`ShutdownSignal`] and
+//! stop, then the flush hooks of every component run, e.g. persisting trees,
+//! flushing hybrid trees or committing roots.
+//!
+//! Hooks run in reverse registration order, so a component registered after
+//! the ones it depends on is flushed before them. Stores write the data a
+//! commit points to before the commit itself, a tree root for instance, so
+//! a process killed before or during the hooks recovers from the last
+//! completed commit.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+/// Cheap to clone handle telling whether shutdown was requested. Setting it
+/// only stores an atomic flag, which is safe from a SIGTERM handler.
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownSignal(Arc<AtomicBool>);
+
+impl ShutdownSignal {
+    pub fn trigger(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+type Hook = Box<dyn FnOnce() -> anyhow::Result<()> + Send>;
+
+#[derive(Default)]
+pub struct Shutdown {
+    signal: ShutdownSignal,
+    hooks: Vec<(String, Hook)>,
+}
+
+impl Shutdown {
+    pub fn signal(&self) -> ShutdownSignal {
+        self.signal.clone()
+    }
+
+    /// Registers `hook` to run on shutdown.
+    pub fn on_shutdown<F>(&mut self, name: &str, hook: F)
+    where
+        F: FnOnce() -> anyhow::Result<()> + Send + 'static,
+    {
+        self.hooks.push((name.to_string(), Box::new(hook)));
+    }
+
+    /// Triggers the signal and runs every hook, even after one fails,
+    /// returning the names of the failed hooks along with their error.
+    pub fn shutdown(self) -> Vec<(String, anyhow::Error)> {
+        self.signal.trigger();
+
+        let mut failed = Vec::new();
+        for (name, hook) in self.hooks.into_iter().rev() {
+            if let Err(e) = hook() {
+                failed.push((name, e));
+            }
+        }
+
+        failed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::bail;
+    use mssmt::{
+        store::MemoryKv,
+        tree::{NodeHash, Tree},
+    };
+
+    use super::Shutdown;
+
+    #[test]
+    fn flush_on_shutdown() {
+        let mut shutdown = Shutdown::default();
+        let signal = shutdown.signal();
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let kv = Arc::new(Mutex::new(MemoryKv::default()));
+        let mut ms_tree = Tree::init();
+        ms_tree.insert(&NodeHash::new([1; 32]), [1; 32], 10).unwrap();
+        let root = ms_tree.root_hash();
+
+        let (hook_kv, hook_order) = (kv.clone(), order.clone());
+        shutdown.on_shutdown("tree", move || {
+            hook_order.lock().unwrap().push("tree");
+            ms_tree.persist(&mut *hook_kv.lock().unwrap()).map(|_| ())
+        });
+        let hook_order = order.clone();
+        shutdown.on_shutdown("courier", move || {
+            hook_order.lock().unwrap().push("courier");
+            bail!("Courier unreachable")
+        });
+
+        assert!(!signal.is_triggered());
+        let failed = shutdown.shutdown();
+        assert!(signal.is_triggered());
+
+        // A failed hook doesn't prevent the others from running.
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, "courier");
+        assert_eq!(*order.lock().unwrap(), vec!["courier", "tree"]);
+        assert_eq!(Tree::load(&*kv.lock().unwrap()).unwrap().root_hash(), root);
+    }
+}
@@ -0,0 +1,202 @@
+//! Supply commitments: the issuer of an asset group commits to every mint,
+//! burn and ignored output in three MS-SMTs, whose roots are the leaves of a
+//! root tree. The issuer signs that root and anchors it on chain, holders
+//! checking the total supply against it.
+
+use anyhow::bail;
+use bitcoin::{
+    TapNodeHash,
+    hashes::{Hash, HashEngine, sha256},
+    secp256k1::{Keypair, Message, Secp256k1, Signing, Verification, XOnlyPublicKey, schnorr::Signature},
+};
+use mssmt::tree::{NodeHash, Tree};
+
+mod delegation;
+mod ignore;
+
+pub use delegation::Delegation;
+pub use ignore::{IgnoreTuple, SignedIgnoreTuple};
+
+const SUPPLY_TAG: &[u8] = b"taproot-assets/supply-commitment";
+
+/// BIP-340 style tagged hash of `data`, as a message to sign.
+fn tagged_message(tag: &[u8], data: &[&[u8]]) -> Message {
+    let tag = sha256::Hash::hash(tag);
+
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_byte_array());
+    engine.input(tag.as_byte_array());
+    for part in data {
+        engine.input(part);
+    }
+
+    Message::from_digest(sha256::Hash::from_engine(engine).to_byte_array())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SupplySubTree {
+    Mint,
+    Burn,
+    Ignore,
+}
+
+impl SupplySubTree {
+    pub const ALL: [SupplySubTree; 3] = [Self::Mint, Self::Burn, Self::Ignore];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Mint => "mint_supply",
+            Self::Burn => "burn",
+            Self::Ignore => "ignore",
+        }
+    }
+
+    /// Key of the sub tree root in the root tree, the sha256 of its name.
+    pub fn key(&self) -> NodeHash {
+        sha256::Hash::hash(self.name().as_bytes()).into()
+    }
+}
+
+/// Root tree of the given sub tree roots, in [`SupplySubTree::ALL`] order.
+fn root_tree(sub_roots: [(NodeHash, u64); 3]) -> anyhow::Result<Tree> {
+    let mut tree = Tree::init();
+    for (sub, (root, sum)) in SupplySubTree::ALL.iter().zip(sub_roots) {
+        tree.insert(&sub.key(), root.to_byte_array(), sum)?;
+    }
+
+    Ok(tree)
+}
+
+pub struct SupplyTree {
+    mint: Tree,
+    burn: Tree,
+    ignore: Tree,
+}
+
+impl Default for SupplyTree {
+    fn default() -> Self {
+        SupplyTree { mint: Tree::init(), burn: Tree::init(), ignore: Tree::init() }
+    }
+}
+
+impl SupplyTree {
+    pub fn sub_tree(&self, sub: SupplySubTree) -> &Tree {
+        match sub {
+            SupplySubTree::Mint => &self.mint,
+            SupplySubTree::Burn => &self.burn,
+            SupplySubTree::Ignore => &self.ignore,
+        }
+    }
+
+    /// Adds `amount` units to `sub` under `key`, `value` committing to the
+    /// mint, burn or ignore leaf itself.
+    pub fn insert(&mut self, sub: SupplySubTree, key: &NodeHash, value: [u8; 32], amount: u64) -> anyhow::Result<()> {
+        let tree = match sub {
+            SupplySubTree::Mint => &mut self.mint,
+            SupplySubTree::Burn => &mut self.burn,
+            SupplySubTree::Ignore => &mut self.ignore,
+        };
+
+        tree.insert(key, value, amount)
+    }
+
+    /// Removes the output of `signed` from the supply, once checked it was
+    /// signed by `issuer`.
+    pub fn ignore<C: Verification>(&mut self, secp: &Secp256k1<C>, signed: &SignedIgnoreTuple, issuer: &XOnlyPublicKey) -> anyhow::Result<()> {
+        if !signed.verify(secp, issuer) {
+            bail!("Ignore tuple for {} not signed by the issuer", signed.tuple.prev_out);
+        }
+
+        self.insert(SupplySubTree::Ignore, &signed.tuple.leaf_key(), signed.leaf_value(), signed.tuple.amount)
+    }
+
+    pub fn sub_roots(&self) -> [(NodeHash, u64); 3] {
+        SupplySubTree::ALL.map(|sub| {
+            let tree = self.sub_tree(sub);
+            (tree.root_hash(), tree.root_sum())
+        })
+    }
+
+    pub fn root(&self) -> anyhow::Result<(NodeHash, u64)> {
+        let tree = root_tree(self.sub_roots())?;
+        Ok((tree.root_hash(), tree.root_sum()))
+    }
+
+    /// Units minted and neither burnt nor ignored, `None` if more were
+    /// removed than minted.
+    pub fn outstanding_supply(&self) -> Option<u64> {
+        self.mint.root_sum().checked_sub(self.burn.root_sum())?.checked_sub(self.ignore.root_sum())
+    }
+
+    /// Signs the current root with the issuer key.
+    pub fn commit<C: Signing>(&self, secp: &Secp256k1<C>, issuer: &Keypair) -> anyhow::Result<SupplyCommitment> {
+        let (root, sum) = self.root()?;
+        let signature = secp.sign_schnorr_no_aux_rand(&SupplyCommitment::message(&root, sum), issuer);
+
+        Ok(SupplyCommitment { root, sum, signature })
+    }
+}
+
+/// Signed root of a [`SupplyTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SupplyCommitment {
+    pub root: NodeHash,
+    pub sum: u64,
+    pub signature: Signature,
+}
+
+impl SupplyCommitment {
+    fn message(root: &NodeHash, sum: u64) -> Message {
+        tagged_message(SUPPLY_TAG, &[root.as_bytes(), &sum.to_be_bytes()])
+    }
+
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, issuer: &XOnlyPublicKey) -> bool {
+        secp.verify_schnorr(&self.signature, &Self::message(&self.root, self.sum), issuer).is_ok()
+    }
+
+    /// Checks that the commitment is the root of the given sub tree roots,
+    /// in [`SupplySubTree::ALL`] order.
+    pub fn verify_sub_roots(&self, sub_roots: [(NodeHash, u64); 3]) -> bool {
+        root_tree(sub_roots).is_ok_and(|tree| (tree.root_hash(), tree.root_sum()) == (self.root, self.sum))
+    }
+
+    /// Root to anchor the commitment with, as the commitment root of an
+    /// anchor output.
+    pub fn anchor_root(&self) -> TapNodeHash {
+        TapNodeHash::from_byte_array(self.root.to_byte_array())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::{Keypair, Secp256k1};
+    use mssmt::tree::NodeHash;
+
+    use super::{SupplySubTree, SupplyTree};
+
+    #[test]
+    fn commit_and_verify() {
+        let secp = Secp256k1::new();
+        let issuer = Keypair::from_seckey_slice(&secp, &[1; 32]).unwrap();
+        let other = Keypair::from_seckey_slice(&secp, &[2; 32]).unwrap();
+
+        let mut supply = SupplyTree::default();
+        supply.insert(SupplySubTree::Mint, &NodeHash::new([1; 32]), [1; 32], 1_000).unwrap();
+        supply.insert(SupplySubTree::Mint, &NodeHash::new([2; 32]), [2; 32], 500).unwrap();
+        supply.insert(SupplySubTree::Burn, &NodeHash::new([3; 32]), [3; 32], 200).unwrap();
+        supply.insert(SupplySubTree::Ignore, &NodeHash::new([4; 32]), [4; 32], 100).unwrap();
+        assert_eq!(supply.outstanding_supply(), Some(1_200));
+
+        let commitment = supply.commit(&secp, &issuer).unwrap();
+        assert_eq!(commitment.sum, 1_800);
+        assert!(commitment.verify(&secp, &issuer.x_only_public_key().0));
+        assert!(!commitment.verify(&secp, &other.x_only_public_key().0));
+        assert!(commitment.verify_sub_roots(supply.sub_roots()));
+
+        let sub_roots = supply.sub_roots();
+        supply.insert(SupplySubTree::Burn, &NodeHash::new([5; 32]), [5; 32], 2_000).unwrap();
+        assert_eq!(supply.outstanding_supply(), None);
+        assert!(!commitment.verify_sub_roots(supply.sub_roots()));
+        assert!(commitment.verify_sub_roots(sub_roots));
+    }
+}
@@ -0,0 +1,92 @@
+//! Delegation of supply signing to an operational key, so the group key can
+//! stay offline. The group key signs the delegation key once, and that reveal
+//! is published along with the commitments it signs.
+
+use anyhow::bail;
+use bitcoin::secp256k1::{Keypair, Secp256k1, Signing, Verification, XOnlyPublicKey, schnorr::Signature};
+
+use super::tagged_message;
+
+const DELEGATION_TAG: &[u8] = b"taproot-assets/supply-delegation";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delegation {
+    pub delegation_key: XOnlyPublicKey,
+
+    /// Signature of the delegation key by the group key.
+    pub signature: Signature,
+}
+
+impl Delegation {
+    pub fn new<C: Signing>(secp: &Secp256k1<C>, group_key: &Keypair, delegation_key: XOnlyPublicKey) -> Self {
+        let signature = secp.sign_schnorr_no_aux_rand(&tagged_message(DELEGATION_TAG, &[&delegation_key.serialize()]), group_key);
+        Delegation { delegation_key, signature }
+    }
+
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, group_key: &XOnlyPublicKey) -> bool {
+        secp.verify_schnorr(&self.signature, &tagged_message(DELEGATION_TAG, &[&self.delegation_key.serialize()]), group_key).is_ok()
+    }
+
+    /// Returns the key supply updates of `group_key` are signed with, once
+    /// checked the group key delegated to it. Pass it to
+    /// [`SupplyCommitment::verify`](super::SupplyCommitment::verify) and
+    /// [`SupplyTree::ignore`](super::SupplyTree::ignore).
+    pub fn signing_key<C: Verification>(&self, secp: &Secp256k1<C>, group_key: &XOnlyPublicKey) -> anyhow::Result<XOnlyPublicKey> {
+        if !self.verify(secp, group_key) {
+            bail!("Key {} wasn't delegated by group key {}", self.delegation_key, group_key);
+        }
+
+        Ok(self.delegation_key)
+    }
+
+    pub fn serialize(&self) -> [u8; 96] {
+        let mut bytes = [0; 96];
+        bytes[..32].copy_from_slice(&self.delegation_key.serialize());
+        bytes[32..].copy_from_slice(self.signature.as_ref());
+        bytes
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() != 96 {
+            bail!("Delegation of {} bytes instead of 96", bytes.len());
+        }
+
+        Ok(Delegation {
+            delegation_key: XOnlyPublicKey::from_slice(&bytes[..32]).map_err(anyhow::Error::msg)?,
+            signature: Signature::from_slice(&bytes[32..]).map_err(anyhow::Error::msg)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::{Keypair, Secp256k1};
+    use mssmt::tree::NodeHash;
+
+    use super::Delegation;
+    use crate::supply::{SupplySubTree, SupplyTree};
+
+    #[test]
+    fn delegated_commitment() {
+        let secp = Secp256k1::new();
+        let group = Keypair::from_seckey_slice(&secp, &[1; 32]).unwrap();
+        let operator = Keypair::from_seckey_slice(&secp, &[2; 32]).unwrap();
+        let group_key = group.x_only_public_key().0;
+
+        let delegation = Delegation::new(&secp, &group, operator.x_only_public_key().0);
+        assert_eq!(Delegation::from_slice(&delegation.serialize()).unwrap(), delegation);
+
+        let signing_key = delegation.signing_key(&secp, &group_key).unwrap();
+        assert!(delegation.signing_key(&secp, &operator.x_only_public_key().0).is_err());
+
+        // Self delegation by the operator isn't accepted for the group.
+        let forged = Delegation::new(&secp, &operator, operator.x_only_public_key().0);
+        assert!(forged.signing_key(&secp, &group_key).is_err());
+
+        let mut supply = SupplyTree::default();
+        supply.insert(SupplySubTree::Mint, &NodeHash::new([1; 32]), [1; 32], 100).unwrap();
+        let commitment = supply.commit(&secp, &operator).unwrap();
+        assert!(commitment.verify(&secp, &signing_key));
+        assert!(!commitment.verify(&secp, &group_key));
+    }
+}
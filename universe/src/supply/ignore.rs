@@ -0,0 +1,188 @@
+//! Ignore tuples: outputs the issuer declares removed from the supply, e.g.
+//! assets sent to a key nobody controls.
+
+use anyhow::{bail, Context};
+use asset::id::AssetId;
+use bitcoin::{
+    OutPoint, Txid,
+    hashes::{Hash, sha256},
+    secp256k1::{Keypair, Secp256k1, Signing, Verification, XOnlyPublicKey, schnorr::Signature},
+};
+use mssmt::tree::NodeHash;
+use tlv::{Reader, Writer};
+
+use super::tagged_message;
+
+const IGNORE_TAG: &[u8] = b"taproot-assets/ignore-tuple";
+
+const PREV_OUT_TYPE: u64 = 0;
+const ASSET_ID_TYPE: u64 = 2;
+const SCRIPT_KEY_TYPE: u64 = 4;
+const AMOUNT_TYPE: u64 = 6;
+
+const TUPLE_TYPE: u64 = 0;
+const SIGNATURE_TYPE: u64 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IgnoreTuple {
+    pub prev_out: OutPoint,
+    pub asset_id: AssetId,
+    pub script_key: XOnlyPublicKey,
+    pub amount: u64,
+}
+
+fn outpoint_bytes(outpoint: &OutPoint) -> [u8; 36] {
+    let mut bytes = [0; 36];
+    bytes[..32].copy_from_slice(outpoint.txid.as_byte_array());
+    bytes[32..].copy_from_slice(&outpoint.vout.to_be_bytes());
+    bytes
+}
+
+impl IgnoreTuple {
+    /// Key of the tuple in the ignore tree, the sha256 of the outpoint and
+    /// script key.
+    pub fn leaf_key(&self) -> NodeHash {
+        let mut data = outpoint_bytes(&self.prev_out).to_vec();
+        data.extend_from_slice(&self.script_key.serialize());
+
+        sha256::Hash::hash(&data).into()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer
+            .put(PREV_OUT_TYPE, &outpoint_bytes(&self.prev_out))
+            .and_then(|w| w.put(ASSET_ID_TYPE, &self.asset_id.to_byte_array()))
+            .and_then(|w| w.put(SCRIPT_KEY_TYPE, &self.script_key.serialize()))
+            .and_then(|w| w.put(AMOUNT_TYPE, &self.amount))
+            .expect("types are written in order");
+
+        writer.finish()
+    }
+
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        let (mut prev_out, mut asset_id, mut script_key, mut amount) = (None, None, None, None);
+
+        for record in Reader::new(bytes).records()? {
+            match record.tlv_type {
+                PREV_OUT_TYPE => {
+                    let bytes: [u8; 36] = record.decode()?;
+                    let txid = Txid::from_byte_array(bytes[..32].try_into()?);
+                    prev_out = Some(OutPoint::new(txid, u32::from_be_bytes(bytes[32..].try_into()?)));
+                },
+                ASSET_ID_TYPE => asset_id = Some(AssetId::from(record.decode::<[u8; 32]>()?)),
+                SCRIPT_KEY_TYPE => script_key = Some(XOnlyPublicKey::from_slice(record.value).map_err(anyhow::Error::msg)?),
+                AMOUNT_TYPE => amount = Some(record.decode()?),
+                _ if record.is_required() => bail!("Unknown even type {} in ignore tuple", record.tlv_type),
+                _ => {},
+            }
+        }
+
+        Ok(IgnoreTuple {
+            prev_out: prev_out.context("Missing ignore tuple outpoint")?,
+            asset_id: asset_id.context("Missing ignore tuple asset id")?,
+            script_key: script_key.context("Missing ignore tuple script key")?,
+            amount: amount.context("Missing ignore tuple amount")?,
+        })
+    }
+
+    pub fn sign<C: Signing>(self, secp: &Secp256k1<C>, issuer: &Keypair) -> SignedIgnoreTuple {
+        let signature = secp.sign_schnorr_no_aux_rand(&tagged_message(IGNORE_TAG, &[&self.encode()]), issuer);
+        SignedIgnoreTuple { tuple: self, signature }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignedIgnoreTuple {
+    pub tuple: IgnoreTuple,
+    pub signature: Signature,
+}
+
+impl SignedIgnoreTuple {
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, issuer: &XOnlyPublicKey) -> bool {
+        secp.verify_schnorr(&self.signature, &tagged_message(IGNORE_TAG, &[&self.tuple.encode()]), issuer).is_ok()
+    }
+
+    /// Value of the tuple leaf, committing to the tuple and its signature.
+    pub fn leaf_value(&self) -> [u8; 32] {
+        sha256::Hash::hash(&self.encode()).to_byte_array()
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        writer
+            .record(TUPLE_TYPE, &self.tuple.encode())
+            .and_then(|w| w.put(SIGNATURE_TYPE, self.signature.as_ref()))
+            .expect("types are written in order");
+
+        writer.finish()
+    }
+
+    pub fn decode(bytes: &[u8]) -> anyhow::Result<Self> {
+        let (mut tuple, mut signature) = (None, None);
+
+        for record in Reader::new(bytes).records()? {
+            match record.tlv_type {
+                TUPLE_TYPE => tuple = Some(IgnoreTuple::decode(record.value)?),
+                SIGNATURE_TYPE => signature = Some(Signature::from_slice(record.value).map_err(anyhow::Error::msg)?),
+                _ if record.is_required() => bail!("Unknown even type {} in signed ignore tuple", record.tlv_type),
+                _ => {},
+            }
+        }
+
+        Ok(SignedIgnoreTuple {
+            tuple: tuple.context("Missing ignore tuple")?,
+            signature: signature.context("Missing ignore tuple signature")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asset::id::AssetId;
+    use bitcoin::{
+        OutPoint, Txid,
+        hashes::Hash,
+        secp256k1::{Keypair, Secp256k1},
+    };
+    use tlv::Writer;
+
+    use super::{IgnoreTuple, SignedIgnoreTuple};
+    use crate::supply::{SupplySubTree, SupplyTree};
+
+    #[test]
+    fn sign_encode_and_ignore() {
+        let secp = Secp256k1::new();
+        let issuer = Keypair::from_seckey_slice(&secp, &[1; 32]).unwrap();
+        let other = Keypair::from_seckey_slice(&secp, &[2; 32]).unwrap();
+
+        let tuple = IgnoreTuple {
+            prev_out: OutPoint::new(Txid::from_byte_array([3; 32]), 1),
+            asset_id: AssetId::new([4; 32]),
+            script_key: other.x_only_public_key().0,
+            amount: 250,
+        };
+        assert_eq!(IgnoreTuple::decode(&tuple.encode()).unwrap(), tuple);
+
+        let signed = tuple.sign(&secp, &issuer);
+        assert_eq!(SignedIgnoreTuple::decode(&signed.encode()).unwrap(), signed);
+        assert!(signed.verify(&secp, &issuer.x_only_public_key().0));
+
+        // Unknown odd types are skipped, even ones rejected.
+        let mut encoded = tuple.encode();
+        let mut writer = Writer::new();
+        writer.record(7, &[1]).unwrap();
+        encoded.extend(writer.finish());
+        assert_eq!(IgnoreTuple::decode(&encoded).unwrap(), tuple);
+        let mut writer = Writer::new();
+        writer.record(8, &[1]).unwrap();
+        encoded.extend(writer.finish());
+        assert!(IgnoreTuple::decode(&encoded).is_err());
+
+        let mut supply = SupplyTree::default();
+        supply.insert(SupplySubTree::Mint, &tuple.leaf_key(), [0; 32], 1_000).unwrap();
+        assert!(supply.ignore(&secp, &tuple.sign(&secp, &other), &issuer.x_only_public_key().0).is_err());
+        supply.ignore(&secp, &signed, &issuer.x_only_public_key().0).unwrap();
+        assert_eq!(supply.outstanding_supply(), Some(750));
+    }
+}